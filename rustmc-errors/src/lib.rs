@@ -1,15 +1,118 @@
+use std::{error::Error, fmt, io};
+
 #[derive(Debug)]
 pub enum PacketError {
     InvalidPacketID,
     InvalidPacketData,
     ErrorFormattingPacket,
     ErrorSendingPacket,
+    UnknownPlayer,
+    Io(io::ErrorKind),
+}
+
+impl From<io::Error> for PacketError {
+    fn from(err: io::Error) -> Self {
+        PacketError::Io(err.kind())
+    }
 }
 
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketError::InvalidPacketID => write!(f, "invalid packet id"),
+            PacketError::InvalidPacketData => write!(f, "invalid packet data"),
+            PacketError::ErrorFormattingPacket => write!(f, "failed to format packet"),
+            PacketError::ErrorSendingPacket => write!(f, "failed to send packet"),
+            PacketError::UnknownPlayer => write!(f, "unknown player"),
+            PacketError::Io(kind) => write!(f, "i/o error: {}", kind),
+        }
+    }
+}
+
+impl Error for PacketError {}
+
 #[derive(Debug)]
 pub enum ConnectionError {
     InvalidHandshake,
     InvalidLogin,
     InvalidStatus,
     InvalidPlay,
-}
\ No newline at end of file
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionError::InvalidHandshake => write!(f, "invalid handshake"),
+            ConnectionError::InvalidLogin => write!(f, "invalid login"),
+            ConnectionError::InvalidStatus => write!(f, "invalid status request"),
+            ConnectionError::InvalidPlay => write!(f, "invalid play-state packet"),
+        }
+    }
+}
+
+impl Error for ConnectionError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UUIDError {
+    InvalidLength,
+    InvalidCharacter,
+}
+
+impl fmt::Display for UUIDError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UUIDError::InvalidLength => write!(f, "invalid uuid length"),
+            UUIDError::InvalidCharacter => write!(f, "invalid character in uuid"),
+        }
+    }
+}
+
+impl Error for UUIDError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_error_keeps_its_kind() {
+        let err = PacketError::from(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert!(matches!(err, PacketError::Io(io::ErrorKind::BrokenPipe)));
+    }
+
+    #[test]
+    fn packet_errors_display_as_sentences() {
+        assert_eq!(PacketError::InvalidPacketID.to_string(), "invalid packet id");
+        assert_eq!(PacketError::InvalidPacketData.to_string(), "invalid packet data");
+        assert_eq!(PacketError::ErrorFormattingPacket.to_string(), "failed to format packet");
+        assert_eq!(PacketError::ErrorSendingPacket.to_string(), "failed to send packet");
+        assert_eq!(PacketError::UnknownPlayer.to_string(), "unknown player");
+        assert_eq!(
+            PacketError::Io(io::ErrorKind::BrokenPipe).to_string(),
+            "i/o error: broken pipe"
+        );
+    }
+
+    #[test]
+    fn connection_errors_display_as_sentences() {
+        assert_eq!(ConnectionError::InvalidHandshake.to_string(), "invalid handshake");
+        assert_eq!(ConnectionError::InvalidLogin.to_string(), "invalid login");
+        assert_eq!(ConnectionError::InvalidStatus.to_string(), "invalid status request");
+        assert_eq!(ConnectionError::InvalidPlay.to_string(), "invalid play-state packet");
+    }
+
+    #[test]
+    fn uuid_errors_display_as_sentences() {
+        assert_eq!(UUIDError::InvalidLength.to_string(), "invalid uuid length");
+        assert_eq!(UUIDError::InvalidCharacter.to_string(), "invalid character in uuid");
+    }
+
+    #[test]
+    fn errors_can_be_boxed() {
+        let errors: Vec<Box<dyn Error>> = vec![
+            Box::new(PacketError::InvalidPacketID),
+            Box::new(ConnectionError::InvalidLogin),
+        ];
+
+        assert_eq!(errors.len(), 2);
+    }
+}
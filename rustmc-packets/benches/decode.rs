@@ -0,0 +1,60 @@
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rustmc_packets::{
+    server::handshake::HandshakePacket,
+    types::{deserialize_string, serialize_string, VarInt},
+    Packet, PacketFormatter,
+};
+
+fn handshake() -> HandshakePacket {
+    HandshakePacket {
+        protocol_version: VarInt(764),
+        server_address: "play.example.com".into(),
+        server_port: 25565,
+        next_state: VarInt(2),
+    }
+}
+
+fn read_varint(c: &mut Criterion) {
+    let data = PacketFormatter::write_varint(i32::MAX as usize);
+    assert_eq!(
+        PacketFormatter::read_varint(&mut BytesMut::from(&data[..])),
+        Some((i32::MAX as usize, 5))
+    );
+
+    c.bench_function("read_varint", |b| {
+        b.iter(|| PacketFormatter::read_varint(&mut BytesMut::from(black_box(&data[..]))))
+    });
+}
+
+fn string_decode(c: &mut Criterion) {
+    let mut data = Vec::new();
+    serialize_string("play.example.com", &mut data);
+    assert_eq!(
+        deserialize_string(&mut BytesMut::from(&data[..])).as_deref(),
+        Some("play.example.com")
+    );
+
+    c.bench_function("deserialize_string", |b| {
+        b.iter(|| deserialize_string(&mut BytesMut::from(black_box(&data[..]))))
+    });
+}
+
+fn handshake_decode(c: &mut Criterion) {
+    let data = handshake().serialize();
+
+    // The buffer-based path must decode exactly what the Vec-based path does.
+    let decoded = HandshakePacket::decode(&mut BytesMut::from(&data[..])).unwrap();
+    let deserialized = HandshakePacket::deserialize(data.clone()).unwrap();
+    assert_eq!(decoded.serialize(), deserialized.serialize());
+
+    c.bench_function("HandshakePacket::deserialize", |b| {
+        b.iter(|| HandshakePacket::deserialize(black_box(data.clone())))
+    });
+    c.bench_function("HandshakePacket::decode", |b| {
+        b.iter(|| HandshakePacket::decode(&mut BytesMut::from(black_box(&data[..]))))
+    });
+}
+
+criterion_group!(benches, read_varint, string_decode, handshake_decode);
+criterion_main!(benches);
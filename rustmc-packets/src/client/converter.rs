@@ -1,77 +1,183 @@
 use std::{sync::Arc, collections::HashMap};
 
-use once_cell::sync::Lazy;
+use bytes::BytesMut;
 use tokio::sync::Mutex;
 
-use crate::Packet;
+use crate::{ConnectionState, Packet};
 
-/// A vector of boxed packets.
-type PacketVec = HashMap<u8, Box<dyn Packet + 'static>>;
+/// A function decoding the data of a packet into a new boxed packet of a registered type.
+pub type PacketDeserializer = fn(&mut BytesMut) -> Option<Box<dyn Packet>>;
 
-/// A vector of boxed packets wrapped in a mutex.
-static mut CLIENT_PACKETS: Lazy<Arc<Mutex<PacketVec>>>= Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+/// A map of packet deserializers, keyed by the connection state and ID they are registered under.
+type PacketVec = HashMap<(ConnectionState, u8), PacketDeserializer>;
 
-pub struct PacketByteConverter;
+/// A registry of the packets that can be received.
+///
+/// Packet IDs are only unique within a connection state, so every packet is registered under the
+/// state it belongs to. Cloning the converter shares the same registry.
+#[derive(Clone, Default)]
+pub struct PacketByteConverter {
+    packets: Arc<Mutex<PacketVec>>,
+}
+
+/// Decodes the data in `buffer` as a `P` and boxes it.
+fn deserialize_boxed<P>(buffer: &mut BytesMut) -> Option<Box<dyn Packet>>
+where
+    P: Packet + 'static,
+{
+    P::decode(buffer).map(|packet| Box::new(packet) as Box<dyn Packet>)
+}
 
 impl PacketByteConverter {
 
-    /// Registers a packet for the client.
-    ///
-    /// This function takes a packet of type `P` that implements the `Packet` trait and registers it for the client.
-    /// The packet is boxed and stored in the `CLIENT_PACKETS` collection, along with its ID.
-    ///
-    /// # Safety
+    /// Creates a new, empty packet registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a packet type for the client.
     ///
-    /// This function is marked as `unsafe` because it performs unsafe operations, such as dereferencing raw pointers.
+    /// This function registers the deserializer of the packet type `P` under the given connection state
+    /// and the packet's ID, so incoming data with that ID can be turned into a `P`.
     ///
     /// # Arguments
     ///
-    /// * `packet` - The packet to register.
+    /// * `state` - The connection state the packet is received in.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustmc_packets::client::converter::register_packet;
-    /// use rustmc_packets::client::Packet;
-    ///
-    /// struct MyPacket;
-    ///
-    /// impl Packet for MyPacket {
-    ///     fn id(&self) -> u32 {
-    ///         42
-    ///     }
-    /// }
+    /// use rustmc_packets::client::converter::PacketByteConverter;
+    /// use rustmc_packets::server::handshake::HandshakePacket;
+    /// use rustmc_packets::ConnectionState;
     ///
     /// #[tokio::main]
     /// async fn main() {
-    ///     register_packet(MyPacket).await;
+    ///     let converter = PacketByteConverter::new();
+    ///     converter.register_packet::<HandshakePacket>(ConnectionState::Handshaking).await;
     /// }
     /// ```
-    pub async unsafe fn register_packet<P>(&self, packet: P)
+    pub async fn register_packet<P>(&self, state: ConnectionState)
     where
         P: Packet + 'static
     {
-        let packet = Box::new(packet);
-        CLIENT_PACKETS.lock().await.insert(packet.id(), packet);
+        self.packets.lock().await.insert((state, P::packet_id()), deserialize_boxed::<P>);
     }
 
-    /// Retrieves a packet from the given `BytesMut` data.
+    /// Retrieves the deserializer registered for a connection state and packet ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The connection state the packet was received in.
+    /// * `packet_id` - The ID of the packet.
+    ///
+    /// # Returns
+    ///
+    /// The registered deserializer, or `None` if no packet is registered for the state and ID.
+    pub async fn get_deserializer(&self, state: ConnectionState, packet_id: u8) -> Option<PacketDeserializer> {
+        self.packets.lock().await.get(&(state, packet_id)).copied()
+    }
+
+    /// Deserializes a packet from its connection state, ID and data.
     /// 
     /// # Arguments
     /// 
-    /// * `data` - The `BytesMut` data containing the packet.
+    /// * `state` - The connection state the packet was received in.
+    /// * `packet_id` - The ID of the packet.
+    /// * `data` - The packet data following the packet ID.
     /// 
     /// # Returns
     /// 
-    /// Returns an `Option<Box<dyn Packet + 'static>>` representing the retrieved packet, or `None` if the packet is not found.
-    /// 
-    /// # Safety
-    /// 
-    /// This function is marked as `unsafe` because it performs raw pointer dereferencing.
-    pub async unsafe fn get_packet(&self, packet_id: u8) -> Option<Box<dyn Packet + 'static>> {
-        return CLIENT_PACKETS.lock().await
-            .get(&packet_id)
-            .map(|box_packet| (*box_packet).clone());
+    /// Returns the deserialized packet, or `None` if no packet is registered for the ID or the data is invalid.
+    pub async fn get_packet(&self, state: ConnectionState, packet_id: u8, data: Vec<u8>) -> Option<Box<dyn Packet + 'static>> {
+        self.get_deserializer(state, packet_id).await?(&mut BytesMut::from(&data[..]))
     }
 
-}
\ No newline at end of file
+    /// Returns every connection state a packet ID is registered under.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet_id` - The ID of the packet.
+    pub async fn registered_states(&self, packet_id: u8) -> Vec<ConnectionState> {
+        self.packets.lock().await
+            .keys()
+            .filter(|(_, id)| *id == packet_id)
+            .map(|(state, _)| *state)
+            .collect()
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::handshake::HandshakePacket,
+        server::login_start::{LoginStartPacket, LoginStartUuid},
+        types::VarInt,
+    };
+
+    #[tokio::test]
+    async fn registered_packet_decodes_wire_bytes() {
+        let converter = PacketByteConverter::new();
+        converter.register_packet::<HandshakePacket>(ConnectionState::Handshaking).await;
+
+        let mut data = vec![0xFC, 0x05, 0x09];
+        data.extend_from_slice(b"localhost");
+        data.extend_from_slice(&[0x63, 0xDD, 0x02]);
+
+        let packet = converter
+            .get_packet(ConnectionState::Handshaking, 0x00, data.clone())
+            .await
+            .expect("handshake should decode");
+
+        let expected = HandshakePacket {
+            protocol_version: VarInt(764),
+            server_address: "localhost".into(),
+            server_port: 25565,
+            next_state: VarInt(2),
+        };
+        assert_eq!(packet.id(), 0x00);
+        assert_eq!(packet.serialize(), expected.serialize());
+        assert!(converter.get_packet(ConnectionState::Handshaking, 0x00, vec![0xFC]).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn states_have_separate_namespaces() {
+        let converter = PacketByteConverter::new();
+        converter.register_packet::<HandshakePacket>(ConnectionState::Handshaking).await;
+        converter.register_packet::<LoginStartPacket>(ConnectionState::Login).await;
+
+        let login_start = LoginStartPacket {
+            name: "Notch".into(),
+            player_uuid: LoginStartUuid(None),
+        };
+        let data = login_start.serialize();
+
+        let login = converter.get_packet(ConnectionState::Login, 0x00, data.clone()).await.unwrap();
+        assert_eq!(login.serialize(), data);
+        assert!(converter.get_deserializer(ConnectionState::Status, 0x00).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn registered_states_lists_every_namespace() {
+        let converter = PacketByteConverter::new();
+        converter.register_packet::<HandshakePacket>(ConnectionState::Handshaking).await;
+        converter.register_packet::<HandshakePacket>(ConnectionState::Status).await;
+
+        let mut states = converter.registered_states(0x00).await;
+        states.sort_by_key(|state| *state as u8);
+
+        assert_eq!(states, vec![ConnectionState::Handshaking, ConnectionState::Status]);
+        assert!(converter.registered_states(0x01).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn clones_share_the_registry() {
+        let converter = PacketByteConverter::new();
+        converter.clone().register_packet::<HandshakePacket>(ConnectionState::Handshaking).await;
+
+        assert!(converter.get_deserializer(ConnectionState::Handshaking, 0x00).await.is_some());
+    }
+}
@@ -1,6 +0,0 @@
-use crate::packet;
-
-packet!(0x00, LoginStartPacket {
-    name: String,
-    player_uuid: String,
-});
\ No newline at end of file
@@ -1,2 +1 @@
 pub mod converter;
-pub mod login_start;
\ No newline at end of file
@@ -0,0 +1,87 @@
+use crate::{ConnectionState, PacketDirection, PROTOCOL_VERSION};
+
+/// A packet whose ID in a protocol version differs from its ID in `PROTOCOL_VERSION`.
+struct Remap {
+    state: ConnectionState,
+    direction: PacketDirection,
+    /// The ID in `PROTOCOL_VERSION`, as written in the `packet!` invocation.
+    id: i32,
+    /// The ID in the version the remap belongs to.
+    version_id: i32,
+}
+
+const fn remap(state: ConnectionState, direction: PacketDirection, id: i32, version_id: i32) -> Remap {
+    Remap { state, direction, id, version_id }
+}
+
+/// The supported protocol versions, with the IDs that differ from `PROTOCOL_VERSION` in each.
+///
+/// Packets missing from a version's list keep their `PROTOCOL_VERSION` ID.
+const ID_TABLES: &[(i32, &[Remap])] = &[
+    (PROTOCOL_VERSION, &[]),
+    // 1.20.1, before the configuration state shifted the play IDs.
+    (
+        763,
+        &[
+            remap(ConnectionState::Play, PacketDirection::Clientbound, 0x1B, 0x1A), // Disconnect
+            remap(ConnectionState::Play, PacketDirection::Clientbound, 0x24, 0x23), // Keep Alive
+            remap(ConnectionState::Play, PacketDirection::Serverbound, 0x14, 0x12), // Keep Alive
+        ],
+    ),
+];
+
+/// Looks up the ID a packet has in a protocol version.
+///
+/// # Arguments
+///
+/// * `version` - The protocol version, as sent in the handshake.
+/// * `state` - The connection state the packet is sent in.
+/// * `direction` - Who sends the packet.
+/// * `id` - The ID of the packet in `PROTOCOL_VERSION`.
+///
+/// # Returns
+///
+/// The ID in the given version, or `None` if the version isn't supported.
+pub fn id_for(version: i32, state: ConnectionState, direction: PacketDirection, id: i32) -> Option<i32> {
+    let (_, remaps) = ID_TABLES.iter().find(|(table_version, _)| *table_version == version)?;
+
+    let remapped = remaps
+        .iter()
+        .find(|remap| remap.state == state && remap.direction == direction && remap.id == id);
+    Some(remapped.map_or(id, |remap| remap.version_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::{
+            disconnect::DisconnectPacket, handshake::HandshakePacket, keep_alive_client::KeepAliveClientPacket,
+            keep_alive_server::KeepAliveServerPacket, status_response::StatusResponsePacket,
+        },
+        Packet,
+    };
+
+    #[test]
+    fn handshake_id_in_the_current_version() {
+        assert_eq!(HandshakePacket::state(), ConnectionState::Handshaking);
+        assert_eq!(HandshakePacket::direction(), PacketDirection::Serverbound);
+        assert_eq!(HandshakePacket::id_for(764), Some(0x00));
+    }
+
+    #[test]
+    fn play_ids_shift_in_older_versions() {
+        assert_eq!(KeepAliveServerPacket::id_for(764), Some(0x24));
+        assert_eq!(KeepAliveServerPacket::id_for(763), Some(0x23));
+        assert_eq!(KeepAliveClientPacket::id_for(763), Some(0x12));
+        assert_eq!(DisconnectPacket::id_for(763), Some(0x1A));
+        assert_eq!(HandshakePacket::id_for(763), Some(0x00));
+        assert_eq!(StatusResponsePacket::id_for(763), Some(0x00));
+    }
+
+    #[test]
+    fn unsupported_versions_have_no_ids() {
+        assert_eq!(HandshakePacket::id_for(47), None);
+        assert_eq!(id_for(0, ConnectionState::Play, PacketDirection::Clientbound, 0x24), None);
+    }
+}
@@ -1,17 +1,106 @@
+use std::{io::Write, sync::Arc};
+
 use bytes::{Buf, BytesMut};
-use tokio::{io::AsyncReadExt, net::TcpStream, sync::MutexGuard};
+use client::converter::PacketByteConverter;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use rustmc_errors::PacketError;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::{mpsc::Sender, Mutex},
+};
+
+/// The protocol version implemented by RustMC (1.20.2).
+pub const PROTOCOL_VERSION: i32 = 764;
+
+/// The Minecraft version name matching `PROTOCOL_VERSION`.
+pub const VERSION_NAME: &str = "1.20.2";
+
+/// The largest frame a client may send, like vanilla (2 MiB).
+pub const MAX_FRAME_LENGTH: usize = 1 << 21;
+
+/// The largest uncompressed size a compressed frame may claim, like vanilla (8 MiB).
+pub const MAX_DATA_LENGTH: usize = 1 << 23;
+
+///
+/// The state of a connection.
+///
+/// Packet IDs are only unique within a state, so the state decides how an incoming packet is interpreted.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    Handshaking,
+    Status,
+    Login,
+    /// Entered after the client acknowledges Login Success, in 1.20.2 and later.
+    Configuration,
+    Play,
+}
+
+///
+/// Who sends a packet.
+///
+/// Packet IDs are only unique within a state and direction, so both are needed to look one up.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketDirection {
+    /// Sent by the client to the server.
+    Serverbound,
+    /// Sent by the server to the client.
+    Clientbound,
+}
 
 ///
 /// This module contains everything related to the Minecraft protocol.
 ///
 /// This trait is used for all packets.
 ///
-pub trait Packet: Sync + Send {
+pub trait Packet: PacketClone + Sync + Send {
     ///
     /// Returns the ID of the packet.
     ///
     fn id(&self) -> u8;
 
+    ///
+    /// Returns the ID of the packet type, without needing an instance of it.
+    ///
+    fn packet_id() -> u8
+    where
+        Self: Sized;
+
+    ///
+    /// Returns the connection state the packet is sent in.
+    ///
+    fn state() -> ConnectionState
+    where
+        Self: Sized;
+
+    ///
+    /// Returns who sends the packet.
+    ///
+    fn direction() -> PacketDirection
+    where
+        Self: Sized;
+
+    ///
+    /// Returns the ID of the packet in the given protocol version, or `None` if the version isn't
+    /// supported. `packet_id` is the ID in `PROTOCOL_VERSION`; the other versions are looked up in
+    /// the tables in `ids`.
+    ///
+    fn id_for(version: i32) -> Option<i32>
+    where
+        Self: Sized,
+    {
+        ids::id_for(version, Self::state(), Self::direction(), Self::packet_id() as i32)
+    }
+
+    ///
+    /// Returns an estimate of the size of the serialized packet, used to pre-size buffers.
+    /// Returns 0 if the packet doesn't know its size up front.
+    ///
+    fn serialized_size_hint(&self) -> usize {
+        0
+    }
+
     ///
     /// This method serializes the packet into a Vec<u8> which is an array of bytes.
     /// This helps the API to send the packet to the server / client.
@@ -23,17 +112,57 @@ pub trait Packet: Sync + Send {
     /// This helps the API to receive the packet from the server / client.
     ///
     fn deserialize(data: Vec<u8>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Self::decode(&mut BytesMut::from(&data[..]))
+    }
+
+    ///
+    /// This method decodes the packet from the front of a buffer, consuming its bytes.
+    /// Unlike `deserialize`, the data isn't copied first, so use this for frames read off the wire.
+    ///
+    fn decode(buffer: &mut BytesMut) -> Option<Self>
     where
         Self: Sized;
 
     ///
     /// This method is used to convert the packet into the format used by Minecraft's protocol.
     ///
+    #[allow(clippy::wrong_self_convention)]
     fn into_protocol_format(&self) -> Vec<u8>
     where
         Self: Sized,
     {
-        PacketFormatter::format_data(self)
+        PacketFormatter::format_data(self, None)
+    }
+}
+
+///
+/// This trait lets boxed packets be cloned.
+///
+/// Trait objects can't require `Clone` directly, so every `Packet` gets this through the
+/// blanket impl below as long as the concrete type is `Clone`.
+///
+pub trait PacketClone {
+    ///
+    /// Clones the packet into a new boxed trait object.
+    ///
+    fn clone_box(&self) -> Box<dyn Packet>;
+}
+
+impl<T> PacketClone for T
+where
+    T: Packet + Clone + 'static,
+{
+    fn clone_box(&self) -> Box<dyn Packet> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Packet> {
+    fn clone(&self) -> Self {
+        self.clone_box()
     }
 }
 
@@ -58,19 +187,108 @@ impl PacketFormatter {
     /// | Packet ID | VarInt     |
     /// | Data      | Byte Array | Depends on the connection state and packet ID, see the sections below
     ///
+    /// Once compression is enabled with a threshold, the frame gains a Data Length field and the
+    /// Packet ID + Data are zlib-compressed if they are at least `threshold` bytes long.
+    ///
+    /// wiki.vg (Packet Format - With Compression):
+    /// | Packet Length | VarInt     | Length of Data Length + compressed length of (Packet ID + Data)
+    /// | Data Length   | VarInt     | Length of uncompressed (Packet ID + Data) or 0
+    /// | Packet ID     | VarInt     | zlib compressed unless Data Length is 0
+    /// | Data          | Byte Array | zlib compressed unless Data Length is 0
+    ///
     #[inline]
-    pub fn format_data<P>(packet: &P) -> Vec<u8>
+    pub fn format_data<P>(packet: &P, compression_threshold: Option<usize>) -> Vec<u8>
     where
-        P: Packet,
+        P: Packet + ?Sized,
     {
-        let data = packet.serialize();
-        let mut formatted_data = Vec::new();
+        let mut data = Vec::with_capacity(5 + packet.serialized_size_hint());
+        data.extend_from_slice(&Self::write_varint(packet.id() as usize)); // Packet ID
+        data.extend_from_slice(&packet.serialize()); // Data
+
+        let data = match compression_threshold {
+            None => data,
+            Some(threshold) if data.len() < threshold => {
+                let mut uncompressed = Self::write_varint(0); // Data Length
+                uncompressed.extend_from_slice(&data);
+                uncompressed
+            }
+            Some(_) => {
+                let mut compressed = Self::write_varint(data.len()); // Data Length
+                compressed.extend_from_slice(&Self::compress(&data));
+                compressed
+            }
+        };
+
+        let mut formatted_data = Vec::with_capacity(5 + data.len());
+        formatted_data.extend_from_slice(&Self::write_varint(data.len())); // Length
+        formatted_data.extend_from_slice(&data);
+
+        formatted_data
+    }
+
+    /// Compresses data with zlib, as used for compressed packet frames.
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).expect("writing to a Vec can't fail");
+        encoder.finish().expect("writing to a Vec can't fail")
+    }
+
+    /// Decompresses zlib data, as found in compressed packet frames.
+    ///
+    /// The client chooses `data_length`, so it is checked before anything is allocated: it must be
+    /// at least `threshold` (smaller frames are sent uncompressed) and at most `MAX_DATA_LENGTH`.
+    /// At most one byte more than `data_length` is inflated, so a zlib bomb can't grow the output.
+    ///
+    /// Returns `None` if the length is out of bounds, the data isn't valid zlib or it doesn't
+    /// inflate to exactly `data_length` bytes.
+    fn decompress(data: &[u8], data_length: usize, threshold: usize) -> Option<BytesMut> {
+        use std::io::Read;
+
+        if data_length < threshold || data_length > MAX_DATA_LENGTH {
+            return None;
+        }
+
+        let mut decompressed = Vec::with_capacity(data_length);
+        ZlibDecoder::new(data)
+            .take(data_length as u64 + 1)
+            .read_to_end(&mut decompressed)
+            .ok()?;
 
-        formatted_data.extend_from_slice(&((data.len() + 1) as u8).to_be_bytes()); // Length of Packet ID + Data
-        formatted_data.extend_from_slice(&packet.id().to_be_bytes()); // Packet ID
-        formatted_data.extend_from_slice(&data); // Data
+        if decompressed.len() != data_length {
+            return None;
+        }
+
+        Some(BytesMut::from(&decompressed[..]))
+    }
+
+    /// Encodes an integer as a variable-length integer.
+    ///
+    /// This function is the counterpart of `read_varint`. The value is written 7 bits at a time, least significant
+    /// group first, with the most significant bit (MSB) of each byte set when more bytes follow.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The integer to encode.
+    ///
+    /// # Returns
+    ///
+    /// The encoded bytes, between 1 and 5 bytes long for any value that fits in an `i32`.
+    #[inline]
+    pub fn write_varint(value: usize) -> Vec<u8> {
+        let mut value = value;
+        let mut bytes = Vec::with_capacity(5);
+
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
 
-        data
+            bytes.push(byte | 0x80);
+        }
     }
 
     /// Reads a variable-length integer from the given buffer.
@@ -78,7 +296,8 @@ impl PacketFormatter {
     /// This function reads a variable-length integer from the provided `buffer` and returns it along with the number of bytes consumed.
     /// The integer is encoded using a variable number of bytes, with each byte representing 7 bits of the integer value.
     /// The most significant bit (MSB) of each byte indicates whether there are more bytes to follow.
-    /// If the buffer does not contain enough bytes to read a complete integer, or if the integer exceeds 5 bytes, `None` is returned.
+    /// If the buffer does not contain enough bytes to read a complete integer, if the integer exceeds 5 bytes,
+    /// or if the decoded value does not fit in an `i32`, `None` is returned.
     ///
     /// # Arguments
     ///
@@ -98,14 +317,18 @@ impl PacketFormatter {
                 return None;
             }
 
-            if buffer.len() == 0 {
+            if buffer.is_empty() {
                 return None;
             }
 
             let byte = buffer.get_u8();
-            result |= ((byte & 0xF7) as usize) << (7 * count);
+            result |= ((byte & 0x7F) as usize) << (7 * count);
 
             if (byte & 0x80) == 0 {
+                if result > i32::MAX as usize {
+                    return None;
+                }
+
                 return Some((result, count + 1));
             }
 
@@ -114,6 +337,14 @@ impl PacketFormatter {
     }
 }
 
+/// A frame read off the wire: the packet ID and the packet data that follows it.
+pub type PacketFrame = (usize, BytesMut);
+
+/// The compression threshold of a connection, shared between the tasks reading and writing it.
+///
+/// `None` until compression is enabled, after which every frame uses the compressed format.
+pub type CompressionThreshold = Arc<Mutex<Option<usize>>>;
+
 /// Represents a packet retriever.
 pub struct PacketRetriever;
 
@@ -122,89 +353,496 @@ pub struct PacketRetriever;
 impl PacketRetriever {
     /// Asynchronously retrieves packets from the player's connection.
     ///
-    /// This function reads data from the player's connection in a loop until no more data is available.
-    /// The read data is stored in a buffer and processed as packets using the `PacketFormatter` struct.
-    /// The processed packets are then passed to the `process_packet` function for further handling.
+    /// This function reads data from the player's connection in a loop until the connection is closed.
+    /// The read data is stored in a buffer and split into frames using the `PacketFormatter` struct.
+    /// Every complete frame is pushed onto `frames` in the order it was received, so the handling of
+    /// packets happens in a separate task and never blocks the network loop.
+    ///
+    /// The compression threshold is checked for every frame, so frames following a change of the
+    /// threshold are decompressed as soon as compression is enabled.
+    ///
+    /// Reading stops when the connection is closed, fails, or the receiving end of `frames` is dropped.
+    /// It also stops at the first malformed frame, since the rest of the stream can't be framed after it.
     ///
     /// # Arguments
     ///
-    /// * `player` - A reference to the `Player` struct representing the player.
+    /// * `connection` - The stream (or read half of a stream) of the connection.
+    /// * `compression_threshold` - The compression threshold of the connection.
+    /// * `frames` - The channel the decoded frames are sent to.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the connection is closed or `frames` is dropped, `PacketError::Io` if reading
+    /// fails, or `PacketError::InvalidPacketData` for a malformed frame. The connection should be
+    /// closed after an error.
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::protocol::PacketHandler;
-    /// use crate::player::Player;
+    /// ```ignore
+    /// use rustmc_packets::PacketRetriever;
+    /// use tokio::sync::mpsc;
     ///
-    /// let packet_handler = PacketHandler::new();
-    /// let player = Player::new();
+    /// let (sender, mut frames) = mpsc::channel(32);
+    /// tokio::spawn(async move {
+    ///     while let Some((packet_id, packet_data)) = frames.recv().await {
+    ///         PacketRetriever.process_packet(&converter, state, packet_id, packet_data).await;
+    ///     }
+    /// });
     ///
-    /// packet_handler.retrieve_packets(&player).await;
+    /// PacketRetriever.retrieve_packets(&mut connection, CompressionThreshold::default(), sender).await?;
     /// ```
-    pub async fn retrieve_packets(&self, connection: &mut MutexGuard<'_, TcpStream>) {
+    pub async fn retrieve_packets<R>(
+        &self,
+        connection: &mut R,
+        compression_threshold: CompressionThreshold,
+        frames: Sender<PacketFrame>,
+    ) -> Result<(), PacketError>
+    where
+        R: AsyncRead + Unpin,
+    {
         let mut buffer = BytesMut::with_capacity(1024);
 
         loop {
             let mut read_buffer: [u8; 1024] = [0; 1024];
             match connection.read(&mut read_buffer).await {
                 Ok(bytes_read) => {
-                    println!("Bytes read: {}", bytes_read);
+                    log::trace!("Bytes read: {}", bytes_read);
 
                     if bytes_read == 0 {
-                        break;
+                        return Ok(());
                     }
 
                     buffer.extend_from_slice(&read_buffer[..bytes_read]);
 
-                    if let Some((length, _)) = PacketFormatter::read_varint(&mut buffer) {
-                        if buffer.len() >= length {
-                            let packet_data = buffer.split_to(length);
-                            self.process_packet(packet_data).await;
-                        } else {
-                            break;
+                    let threshold = *compression_threshold.lock().await;
+                    while let Some(frame) = Self::split_frame(&mut buffer, threshold)? {
+                        if frames.send(frame).await.is_err() {
+                            return Ok(());
                         }
                     }
                 }
-                Err(err) => {
-                    eprintln!(
-                        "Error reading from TcpStream from connection: {:?}",
-                        connection
-                    );
-                    eprintln!("Error: {:?}", err);
-                    break;
-                }
+                Err(err) => return Err(PacketError::from(err)),
+            }
+        }
+    }
+
+    /// Splits the next complete frame off the front of the buffer.
+    ///
+    /// Returns `Ok(None)` and leaves the buffer untouched if it does not hold a whole frame yet.
+    /// With a compression threshold, the frame is read in the compressed format and inflated if needed.
+    ///
+    /// A length prefix that isn't a valid VarInt or is above `MAX_FRAME_LENGTH` is rejected as soon as
+    /// it arrives, without waiting for the rest of the frame. A frame whose data length, compressed
+    /// data or packet ID is invalid is rejected too, with `PacketError::InvalidPacketData`.
+    fn split_frame(
+        buffer: &mut BytesMut,
+        compression_threshold: Option<usize>,
+    ) -> Result<Option<PacketFrame>, PacketError> {
+        let Some((length, length_size)) = Self::peek_frame_length(buffer)? else {
+            return Ok(None);
+        };
+
+        if length > MAX_FRAME_LENGTH {
+            return Err(PacketError::InvalidPacketData);
+        }
+
+        if buffer.len() < length_size + length {
+            return Ok(None);
+        }
+
+        buffer.advance(length_size);
+        let mut packet_data = buffer.split_to(length);
+
+        if let Some(threshold) = compression_threshold {
+            let (data_length, _) =
+                PacketFormatter::read_varint(&mut packet_data).ok_or(PacketError::InvalidPacketData)?;
+
+            // A data length of 0 means the frame was below the threshold and sent uncompressed.
+            if data_length != 0 {
+                packet_data = PacketFormatter::decompress(&packet_data, data_length, threshold)
+                    .ok_or(PacketError::InvalidPacketData)?;
             }
         }
+
+        let (packet_id, _) = PacketFormatter::read_varint(&mut packet_data).ok_or(PacketError::InvalidPacketData)?;
+
+        Ok(Some((packet_id, packet_data)))
+    }
+
+    /// Reads the length prefix of the next frame without consuming it.
+    ///
+    /// Returns `Ok(None)` if the VarInt isn't complete yet, and an error if it is longer than 5 bytes
+    /// or doesn't fit in an `i32`.
+    fn peek_frame_length(buffer: &BytesMut) -> Result<Option<(usize, usize)>, PacketError> {
+        let Some(last) = buffer.iter().take(5).position(|byte| byte & 0x80 == 0) else {
+            return match buffer.len() {
+                0..=4 => Ok(None),
+                _ => Err(PacketError::InvalidPacketData),
+            };
+        };
+
+        PacketFormatter::read_varint(&mut BytesMut::from(&buffer[..=last]))
+            .map(Some)
+            .ok_or(PacketError::InvalidPacketData)
     }
 
     /// Processes a packet asynchronously.
     ///
-    /// This function takes in a `BytesMut` object representing the packet data and prints the received packet and its ID.
-    /// The packet ID is extracted from the first byte of the packet data.
+    /// This function looks up the deserializer registered in the `PacketByteConverter` for the current
+    /// connection state and packet ID, and uses it to turn the packet data into a typed packet, ready to be
+    /// dispatched by the caller.
     ///
     /// # Arguments
     ///
+    /// * `converter` - The registry of packets that can be received.
+    /// * `state` - The current state of the connection.
+    /// * `packet_id` - The ID of the packet.
     /// * `packet_data` - The packet data as a `BytesMut` object.
     ///
+    /// # Returns
+    ///
+    /// The decoded packet, `PacketError::InvalidPacketID` if no packet is registered for the ID,
+    /// or `PacketError::InvalidPacketData` if the data can't be deserialized.
+    ///
     /// # Examples
     ///
-    /// ```
+    /// ```ignore
     /// use bytes::BytesMut;
-    /// use rustmc_packets::PacketProcessor;
+    /// use rustmc_packets::PacketRetriever;
     ///
-    /// let packet_data = BytesMut::from(&[0x01, 0x02, 0x03][..]);
-    /// let processor = PacketProcessor::new();
-    /// processor.process_packet(packet_data).await;
+    /// let packet_data = BytesMut::from(&[0x02, 0x03][..]);
+    /// match PacketRetriever.process_packet(&converter, ConnectionState::Play, 0x01, packet_data).await {
+    ///     Ok(packet) => println!("Received Packet ID: {}", packet.id()),
+    ///     Err(err) => eprintln!("Error processing packet: {}", err),
+    /// }
     /// ```
     #[inline]
-    pub async fn process_packet(&self, packet_data: BytesMut) {
-        println!("Received Packet: {:?}", packet_data);
+    pub async fn process_packet(
+        &self,
+        converter: &PacketByteConverter,
+        state: ConnectionState,
+        packet_id: usize,
+        mut packet_data: BytesMut,
+    ) -> Result<Box<dyn Packet>, PacketError> {
+        let packet_id = u8::try_from(packet_id).map_err(|_| PacketError::InvalidPacketID)?;
+
+        let Some(deserializer) = converter.get_deserializer(state, packet_id).await else {
+            // Only worth scanning the registry when the hint will be printed.
+            if log::log_enabled!(log::Level::Debug) {
+                log::debug!(
+                    "Packet ID {:#04x} isn't registered in {:?}, only in {:?}",
+                    packet_id,
+                    state,
+                    converter.registered_states(packet_id).await
+                );
+            }
+            return Err(PacketError::InvalidPacketID);
+        };
 
-        let packet_id = packet_data.first().map(|&byte| byte).unwrap();
-        println!("Received Packet ID: {}", packet_id);
+        deserializer(&mut packet_data).ok_or(PacketError::InvalidPacketData)
     }
 }
 
 pub mod client;
+pub mod ids;
 pub mod macros;
 pub mod server;
+pub mod types;
+pub mod uuid;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+    };
+
+    packet!((Play, Clientbound, 0x80), FramingTestPacket {
+        value: u8,
+    });
+
+    fn read_varint(bytes: &[u8]) -> Option<(usize, usize)> {
+        PacketFormatter::read_varint(&mut BytesMut::from(bytes))
+    }
+
+    #[test]
+    fn read_varint_decodes_known_values() {
+        assert_eq!(read_varint(&[0x00]), Some((0, 1)));
+        assert_eq!(read_varint(&[0x7F]), Some((127, 1)));
+        assert_eq!(read_varint(&[0x80, 0x01]), Some((128, 2)));
+        assert_eq!(read_varint(&[0xFF, 0x01]), Some((255, 2)));
+        assert_eq!(read_varint(&[0xFF, 0xFF, 0x7F]), Some((2097151, 3)));
+        assert_eq!(
+            read_varint(&[0xFF, 0xFF, 0xFF, 0xFF, 0x07]),
+            Some((i32::MAX as usize, 5))
+        );
+    }
+
+    #[test]
+    fn read_varint_rejects_overflow_and_truncation() {
+        assert_eq!(read_varint(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]), None);
+        assert_eq!(read_varint(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]), None);
+        assert_eq!(read_varint(&[0x80]), None);
+        assert_eq!(read_varint(&[]), None);
+    }
+
+    #[test]
+    fn write_varint_round_trips_through_read_varint() {
+        for value in [0, 1, 127, 128, 255, 300, 25565, 2097151, 2097152, i32::MAX as usize] {
+            let bytes = PacketFormatter::write_varint(value);
+            assert_eq!(read_varint(&bytes), Some((value, bytes.len())));
+        }
+
+        assert_eq!(PacketFormatter::write_varint(300), vec![0xAC, 0x02]);
+        assert_eq!(PacketFormatter::write_varint(i32::MAX as usize).len(), 5);
+    }
+
+    #[test]
+    fn format_data_prefixes_length_and_packet_id() {
+        let packet = FramingTestPacket { value: 0x2A };
+        let data = packet.serialize();
+        let formatted = packet.into_protocol_format();
+
+        // Packet ID 0x80 needs two VarInt bytes.
+        assert_eq!(formatted[0] as usize, 2 + data.len());
+        assert_eq!(&formatted[1..3], &[0x80, 0x01]);
+        assert_eq!(&formatted[3..], &data[..]);
+    }
+
+    #[test]
+    fn compressed_frames_below_the_threshold_are_sent_inline() {
+        let packet = FramingTestPacket { value: 0x2A };
+        let formatted = PacketFormatter::format_data(&packet, Some(256));
+
+        // Length, a Data Length of 0, then the uncompressed Packet ID and Data.
+        assert_eq!(formatted, vec![0x04, 0x00, 0x80, 0x01, 0x2A]);
+
+        let mut buffer = BytesMut::from(&formatted[..]);
+        let (id, data) = PacketRetriever::split_frame(&mut buffer, Some(256)).unwrap().unwrap();
+        assert_eq!((id, &data[..]), (0x80, &[0x2A][..]));
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn compressed_frames_above_the_threshold_round_trip() {
+        use crate::server::status_response::StatusResponsePacket;
+
+        let packet = StatusResponsePacket {
+            json_response: "{\"text\":\"RustMC\"}".repeat(32),
+        };
+        let uncompressed = PacketFormatter::format_data(&packet, None);
+        let compressed = PacketFormatter::format_data(&packet, Some(64));
+        assert!(compressed.len() < uncompressed.len());
+
+        // The Data Length is the size of the uncompressed Packet ID + Data.
+        let mut frame = BytesMut::from(&compressed[..]);
+        PacketFormatter::read_varint(&mut frame).unwrap();
+        let (data_length, _) = PacketFormatter::read_varint(&mut frame).unwrap();
+        assert_eq!(data_length, 1 + packet.serialize().len());
+
+        let mut wire = compressed.clone();
+        wire.extend(PacketFormatter::format_data(&FramingTestPacket { value: 1 }, Some(64)));
+
+        let (sender, mut frames) = mpsc::channel(8);
+        PacketRetriever
+            .retrieve_packets(&mut &wire[..], Arc::new(Mutex::new(Some(64))), sender)
+            .await
+            .unwrap();
+
+        let (id, data) = frames.recv().await.unwrap();
+        assert_eq!((id, data.to_vec()), (0x00, packet.serialize()));
+        let (id, data) = frames.recv().await.unwrap();
+        assert_eq!((id, &data[..]), (0x80, &[0x01][..]));
+    }
+
+    #[test]
+    fn decompress_rejects_lengths_out_of_bounds() {
+        let data = vec![0x2A; 128];
+        let compressed = PacketFormatter::compress(&data);
+
+        assert_eq!(PacketFormatter::decompress(&compressed, 128, 64).unwrap().to_vec(), data);
+        assert!(PacketFormatter::decompress(&compressed, MAX_DATA_LENGTH + 1, 64).is_none());
+        assert!(PacketFormatter::decompress(&compressed, i32::MAX as usize, 64).is_none());
+        assert!(PacketFormatter::decompress(&compressed, 128, 256).is_none());
+    }
+
+    #[test]
+    fn decompress_rejects_a_length_that_does_not_match_the_payload() {
+        let compressed = PacketFormatter::compress(&[0x2A; 128]);
+
+        assert!(PacketFormatter::decompress(&compressed, 127, 64).is_none());
+        assert!(PacketFormatter::decompress(&compressed, 129, 64).is_none());
+
+        // A payload inflating far past its claimed length is cut off instead of inflated whole.
+        let bomb = PacketFormatter::compress(&vec![0; MAX_DATA_LENGTH]);
+        assert!(PacketFormatter::decompress(&bomb, 64, 64).is_none());
+    }
+
+    #[tokio::test]
+    async fn process_packet_decodes_registered_packets() {
+        use crate::{server::handshake::HandshakePacket, types::VarInt};
+
+        let packet = HandshakePacket {
+            protocol_version: VarInt(764),
+            server_address: "localhost".into(),
+            server_port: 25565,
+            next_state: VarInt(1),
+        };
+        let data = packet.serialize();
+        let converter = PacketByteConverter::new();
+        let state = ConnectionState::Handshaking;
+        converter.register_packet::<HandshakePacket>(state).await;
+
+        let decoded = PacketRetriever
+            .process_packet(&converter, state, 0x00, BytesMut::from(&data[..]))
+            .await
+            .unwrap();
+        assert_eq!(decoded.id(), 0x00);
+        assert_eq!(decoded.serialize(), data);
+
+        let unknown = PacketRetriever
+            .process_packet(&converter, state, 0x7E, BytesMut::new())
+            .await;
+        assert!(matches!(unknown, Err(PacketError::InvalidPacketID)));
+
+        let other_state = PacketRetriever
+            .process_packet(&converter, ConnectionState::Login, 0x00, BytesMut::from(&data[..]))
+            .await;
+        assert!(matches!(other_state, Err(PacketError::InvalidPacketID)));
+
+        let truncated = PacketRetriever
+            .process_packet(&converter, state, 0x00, BytesMut::from(&[0xFC][..]))
+            .await;
+        assert!(matches!(truncated, Err(PacketError::InvalidPacketData)));
+    }
+
+    #[tokio::test]
+    async fn process_packet_decodes_by_connection_state() {
+        use crate::{
+            server::handshake::HandshakePacket,
+            server::login_start::{LoginStartPacket, LoginStartUuid},
+            uuid::UUID,
+        };
+
+        let converter = PacketByteConverter::new();
+        let login_start = LoginStartPacket {
+            name: "Notch".into(),
+            player_uuid: LoginStartUuid(Some(UUID::from_username_offline("Notch"))),
+        };
+        let login_data = login_start.serialize();
+        converter.register_packet::<HandshakePacket>(ConnectionState::Handshaking).await;
+        converter.register_packet::<LoginStartPacket>(ConnectionState::Login).await;
+
+        let decoded = PacketRetriever
+            .process_packet(&converter, ConnectionState::Login, 0x00, BytesMut::from(&login_data[..]))
+            .await
+            .unwrap();
+        assert_eq!(decoded.serialize(), login_data);
+
+        // The same bytes are not a valid handshake, so decoding them in the wrong state fails.
+        let wrong_state = PacketRetriever
+            .process_packet(&converter, ConnectionState::Handshaking, 0x00, BytesMut::from(&login_data[..]))
+            .await;
+        assert!(matches!(wrong_state, Err(PacketError::InvalidPacketData)));
+    }
+
+    #[test]
+    fn read_varint_consumes_only_its_own_bytes() {
+        let mut buffer = BytesMut::from(&[0xAC, 0x02, 0x42][..]);
+
+        assert_eq!(PacketFormatter::read_varint(&mut buffer), Some((300, 2)));
+        assert_eq!(&buffer[..], &[0x42]);
+    }
+
+    #[tokio::test]
+    async fn frames_arrive_on_the_channel_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        // Two frames in a single write, the second split across the write boundary.
+        client.write_all(&[0x03, 0x00, 0xAA, 0xBB, 0x02, 0x01]).await.unwrap();
+        client.write_all(&[0xCC]).await.unwrap();
+        drop(client);
+
+        let (sender, mut frames) = mpsc::channel(8);
+        PacketRetriever
+            .retrieve_packets(&mut stream, CompressionThreshold::default(), sender)
+            .await
+            .unwrap();
+
+        let (id, data) = frames.recv().await.unwrap();
+        assert_eq!((id, &data[..]), (0x00, &[0xAA, 0xBB][..]));
+        let (id, data) = frames.recv().await.unwrap();
+        assert_eq!((id, &data[..]), (0x01, &[0xCC][..]));
+        assert!(frames.recv().await.is_none());
+    }
+
+    fn split(bytes: &[u8], compression_threshold: Option<usize>) -> Result<Option<PacketFrame>, PacketError> {
+        PacketRetriever::split_frame(&mut BytesMut::from(bytes), compression_threshold)
+    }
+
+    #[test]
+    fn split_frame_waits_for_incomplete_frames() {
+        assert!(matches!(split(&[], None), Ok(None)));
+        assert!(matches!(split(&[0x80, 0x80], None), Ok(None)));
+        assert!(matches!(split(&[0x03, 0x00, 0xAA], None), Ok(None)));
+
+        let mut buffer = BytesMut::from(&[0x03, 0x00, 0xAA][..]);
+        assert!(matches!(PacketRetriever::split_frame(&mut buffer, None), Ok(None)));
+        assert_eq!(&buffer[..], &[0x03, 0x00, 0xAA]);
+    }
+
+    #[test]
+    fn split_frame_rejects_an_invalid_length() {
+        assert!(matches!(split(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF], None), Err(PacketError::InvalidPacketData)));
+        assert!(matches!(split(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F], None), Err(PacketError::InvalidPacketData)));
+    }
+
+    #[test]
+    fn split_frame_rejects_frames_above_the_maximum_length() {
+        let mut header = PacketFormatter::write_varint(MAX_FRAME_LENGTH + 1);
+        assert!(matches!(split(&header, None), Err(PacketError::InvalidPacketData)));
+
+        header = PacketFormatter::write_varint(MAX_FRAME_LENGTH);
+        assert!(matches!(split(&header, None), Ok(None)));
+    }
+
+    #[test]
+    fn split_frame_rejects_a_bad_packet_id() {
+        assert!(matches!(split(&[0x00], None), Err(PacketError::InvalidPacketData)));
+        assert!(matches!(split(&[0x01, 0x80], None), Err(PacketError::InvalidPacketData)));
+    }
+
+    #[test]
+    fn split_frame_rejects_bad_compressed_data() {
+        // A truncated data length.
+        assert!(matches!(split(&[0x01, 0x80], Some(64)), Err(PacketError::InvalidPacketData)));
+        // A data length with data that isn't zlib.
+        assert!(matches!(split(&[0x03, 0x40, 0x01, 0x02], Some(64)), Err(PacketError::InvalidPacketData)));
+        // A data length below the threshold.
+        let mut data = vec![0x08];
+        data.extend(PacketFormatter::compress(&[0x00; 8]));
+        let mut frame = PacketFormatter::write_varint(data.len());
+        frame.extend(data);
+        assert!(matches!(split(&frame, Some(64)), Err(PacketError::InvalidPacketData)));
+    }
+
+    #[tokio::test]
+    async fn malformed_frames_end_the_stream_without_stalling_earlier_frames() {
+        // A valid frame followed by a frame with no packet ID, in one read.
+        let wire = [0x02, 0x00, 0xAA, 0x00, 0x02, 0x01, 0xBB];
+
+        let (sender, mut frames) = mpsc::channel(8);
+        let result = PacketRetriever
+            .retrieve_packets(&mut &wire[..], CompressionThreshold::default(), sender)
+            .await;
+
+        assert!(matches!(result, Err(PacketError::InvalidPacketData)));
+        let (id, data) = frames.recv().await.unwrap();
+        assert_eq!((id, &data[..]), (0x00, &[0xAA][..]));
+        assert!(frames.recv().await.is_none());
+    }
+}
@@ -1,12 +1,11 @@
 #[macro_export]
 macro_rules! packet {
-    ($id: literal, $name: ident {
+    (($state: ident, $direction: ident, $id: literal), $name: ident {
         $( $field: ident : $ty: ty ),* $(,)?
     }) => {
-        use crate::Packet;
-        use serde::{Serialize, Deserialize};
+        use $crate::Packet;
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone)]
         pub struct $name {
             $( pub $field: $ty ),*
         }
@@ -17,12 +16,34 @@ macro_rules! packet {
                 $id
             }
 
+            fn packet_id() -> u8 {
+                $id
+            }
+
+            fn state() -> $crate::ConnectionState {
+                $crate::ConnectionState::$state
+            }
+
+            fn direction() -> $crate::PacketDirection {
+                $crate::PacketDirection::$direction
+            }
+
+            fn serialized_size_hint(&self) -> usize {
+                0 $( + $crate::types::ProtocolField::size_hint(&self.$field) )*
+            }
+
             fn serialize(&self) -> Vec<u8> {
-                bincode::serialize(self).expect("Failed to serialize packet")
+                #[allow(unused_mut)]
+                let mut data = Vec::with_capacity(self.serialized_size_hint());
+                $( $crate::types::ProtocolField::write_to(&self.$field, &mut data); )*
+                data
             }
 
-            fn deserialize(data: Vec<u8>) -> Option<Self> {
-                bincode::deserialize::<$name>(&data).ok()
+            #[allow(unused_variables)]
+            fn decode(buffer: &mut bytes::BytesMut) -> Option<Self> {
+                Some(Self {
+                    $( $field: <$ty as $crate::types::ProtocolField>::read_from(buffer)?, )*
+                })
             }
 
         }
@@ -0,0 +1,3 @@
+use crate::packet;
+
+packet!((Configuration, Serverbound, 0x02), AcknowledgeFinishConfigurationPacket {});
@@ -0,0 +1,14 @@
+use crate::packet;
+
+packet!((Configuration, Clientbound, 0x01), ConfigurationDisconnectPacket {
+    reason: String,
+});
+
+impl ConfigurationDisconnectPacket {
+    /// Creates a configuration-state disconnect packet showing `message` as a plain text chat component.
+    pub fn from_message(message: &str) -> Self {
+        Self {
+            reason: serde_json::json!({ "text": message }).to_string(),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::packet;
+
+packet!((Play, Clientbound, 0x1B), DisconnectPacket {
+    reason: String,
+});
+
+impl DisconnectPacket {
+    /// Creates a play-state disconnect packet showing `message` as a plain text chat component.
+    pub fn from_message(message: &str) -> Self {
+        Self {
+            reason: serde_json::json!({ "text": message }).to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reason_is_a_text_component() {
+        let packet = DisconnectPacket::from_message("Server \"closed\"");
+        let reason: serde_json::Value = serde_json::from_str(&packet.reason).unwrap();
+
+        assert_eq!(reason["text"], "Server \"closed\"");
+    }
+}
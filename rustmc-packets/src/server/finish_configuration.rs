@@ -0,0 +1,3 @@
+use crate::packet;
+
+packet!((Configuration, Clientbound, 0x02), FinishConfigurationPacket {});
@@ -1,8 +1,69 @@
-use crate::packet;
+use crate::{packet, types::VarInt};
 
-packet!(0x00, HandshakePacket {
-    protocol_version: u16,
+packet!((Handshaking, Serverbound, 0x00), HandshakePacket {
+    protocol_version: VarInt,
     server_address: String,
     server_port: u16,
-    next_state: u8,
-});
\ No newline at end of file
+    next_state: VarInt,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_matches_the_wire_layout() {
+        let packet = HandshakePacket {
+            protocol_version: VarInt(764),
+            server_address: "localhost".into(),
+            server_port: 25565,
+            next_state: VarInt(2),
+        };
+
+        let mut expected = vec![0xFC, 0x05, 0x09];
+        expected.extend_from_slice(b"localhost");
+        expected.extend_from_slice(&[0x63, 0xDD, 0x02]);
+
+        assert_eq!(packet.serialize(), expected);
+
+        let decoded = HandshakePacket::deserialize(expected).unwrap();
+        assert_eq!(decoded.protocol_version, VarInt(764));
+        assert_eq!(decoded.server_address, "localhost");
+        assert_eq!(decoded.server_port, 25565);
+        assert_eq!(decoded.next_state, VarInt(2));
+    }
+
+    #[test]
+    fn decode_agrees_with_deserialize() {
+        let packet = HandshakePacket {
+            protocol_version: VarInt(764),
+            server_address: "localhost".into(),
+            server_port: 25565,
+            next_state: VarInt(1),
+        };
+        let data = packet.serialize();
+
+        let mut buffer = bytes::BytesMut::from(&data[..]);
+        buffer.extend_from_slice(&[0xAA]);
+        let decoded = HandshakePacket::decode(&mut buffer).unwrap();
+
+        assert_eq!(decoded.serialize(), HandshakePacket::deserialize(data).unwrap().serialize());
+        assert_eq!(&buffer[..], &[0xAA]);
+    }
+
+    #[test]
+    fn serialize_pre_sizes_its_buffer() {
+        let packet = HandshakePacket {
+            protocol_version: VarInt(764),
+            server_address: "localhost".into(),
+            server_port: 25565,
+            next_state: VarInt(2),
+        };
+
+        let data = packet.serialize();
+
+        assert_eq!(packet.serialized_size_hint(), 5 + (5 + 9) + 2 + 5);
+        assert_eq!(data.capacity(), packet.serialized_size_hint());
+        assert!(data.len() <= data.capacity());
+    }
+}
@@ -0,0 +1,5 @@
+use crate::packet;
+
+packet!((Play, Serverbound, 0x14), KeepAliveClientPacket {
+    id: i64,
+});
@@ -0,0 +1,5 @@
+use crate::packet;
+
+packet!((Play, Clientbound, 0x24), KeepAliveServerPacket {
+    id: i64,
+});
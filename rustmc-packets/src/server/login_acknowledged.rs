@@ -0,0 +1,3 @@
+use crate::packet;
+
+packet!((Login, Serverbound, 0x03), LoginAcknowledgedPacket {});
@@ -0,0 +1,14 @@
+use crate::packet;
+
+packet!((Login, Clientbound, 0x00), LoginDisconnectPacket {
+    reason: String,
+});
+
+impl LoginDisconnectPacket {
+    /// Creates a login-state disconnect packet showing `message` as a plain text chat component.
+    pub fn from_message(message: &str) -> Self {
+        Self {
+            reason: serde_json::json!({ "text": message }).to_string(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use bytes::{Buf, BytesMut};
+
+use crate::{packet, types::ProtocolField, uuid::UUID};
+
+packet!((Login, Serverbound, 0x00), LoginStartPacket {
+    name: String,
+    player_uuid: LoginStartUuid,
+});
+
+///
+/// The optional UUID ending the Login Start packet.
+///
+/// Its layout changed between versions: 1.19.1 to 1.20.1 prefix it with a boolean, 1.20.2 always
+/// sends it, and older clients leave it out. All three are read, and it is written in the 1.20.2 layout.
+/// The layout is told apart by how many bytes are left, which only works because this is the last
+/// field of the packet, so don't use it anywhere else.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoginStartUuid(pub Option<UUID>);
+
+impl ProtocolField for LoginStartUuid {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        if let Some(uuid) = self.0 {
+            uuid.write_to(buffer);
+        }
+    }
+
+    fn read_from(buffer: &mut BytesMut) -> Option<Self> {
+        let uuid = match buffer.len() {
+            0 => None,
+            1 if buffer[0] == 0x00 => {
+                buffer.advance(1);
+                None
+            }
+            17 if buffer[0] == 0x01 => {
+                buffer.advance(1);
+                Some(UUID::read_from(buffer)?)
+            }
+            _ => Some(UUID::read_from(buffer)?),
+        };
+
+        Some(Self(uuid))
+    }
+
+    fn size_hint(&self) -> usize {
+        if self.0.is_some() {
+            16
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_uuid_layout() {
+        let uuid = UUID::from_username_offline("Notch");
+        let mut name = vec![0x05];
+        name.extend_from_slice(b"Notch");
+
+        // 1.20.2: the UUID always follows the name.
+        let mut current = name.clone();
+        current.extend_from_slice(&uuid.data);
+        // 1.19.1 to 1.20.1: a boolean says whether the UUID follows.
+        let mut prefixed = name.clone();
+        prefixed.push(0x01);
+        prefixed.extend_from_slice(&uuid.data);
+        let mut prefixed_none = name.clone();
+        prefixed_none.push(0x00);
+
+        for (data, expected) in [
+            (current, Some(uuid)),
+            (prefixed, Some(uuid)),
+            (prefixed_none, None),
+            (name.clone(), None),
+        ] {
+            let packet = LoginStartPacket::deserialize(data).unwrap();
+            assert_eq!(packet.name, "Notch");
+            assert_eq!(packet.player_uuid, LoginStartUuid(expected));
+        }
+
+        let packet = LoginStartPacket { name: "Notch".into(), player_uuid: LoginStartUuid(Some(uuid)) };
+        let mut expected = name;
+        expected.extend_from_slice(&uuid.data);
+        assert_eq!(packet.serialize(), expected);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{packet, types::VarInt, uuid::UUID};
+
+packet!((Login, Clientbound, 0x02), LoginSuccessPacket {
+    uuid: UUID,
+    username: String,
+    // Offline mode has no skin or cape properties, so this is always 0.
+    property_count: VarInt,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn login_success_matches_the_wire_layout() {
+        let uuid = UUID::from_username_offline("Notch");
+        let packet = LoginSuccessPacket {
+            uuid,
+            username: "Notch".into(),
+            property_count: VarInt(0),
+        };
+
+        let mut expected = uuid.data.to_vec();
+        expected.push(0x05);
+        expected.extend_from_slice(b"Notch");
+        expected.push(0x00);
+
+        assert_eq!(packet.serialize(), expected);
+    }
+}
@@ -1 +1,16 @@
-pub mod handshake;
\ No newline at end of file
+pub mod acknowledge_finish_configuration;
+pub mod configuration_disconnect;
+pub mod disconnect;
+pub mod finish_configuration;
+pub mod handshake;
+pub mod keep_alive_client;
+pub mod keep_alive_server;
+pub mod login_acknowledged;
+pub mod login_disconnect;
+pub mod login_start;
+pub mod login_success;
+pub mod ping;
+pub mod pong;
+pub mod set_compression;
+pub mod status_request;
+pub mod status_response;
@@ -0,0 +1,5 @@
+use crate::packet;
+
+packet!((Status, Serverbound, 0x01), PingPacket {
+    payload: i64,
+});
@@ -0,0 +1,5 @@
+use crate::packet;
+
+packet!((Status, Clientbound, 0x01), PongPacket {
+    payload: i64,
+});
@@ -0,0 +1,5 @@
+use crate::{packet, types::VarInt};
+
+packet!((Login, Clientbound, 0x03), SetCompressionPacket {
+    threshold: VarInt,
+});
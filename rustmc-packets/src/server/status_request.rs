@@ -0,0 +1,3 @@
+use crate::packet;
+
+packet!((Status, Serverbound, 0x00), StatusRequestPacket {});
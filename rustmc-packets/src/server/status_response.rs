@@ -0,0 +1,5 @@
+use crate::packet;
+
+packet!((Status, Clientbound, 0x00), StatusResponsePacket {
+    json_response: String,
+});
@@ -0,0 +1,343 @@
+use bytes::{Buf, BytesMut};
+
+use crate::PacketFormatter;
+
+///
+/// A value that can be written to and read from Minecraft's wire format.
+///
+/// The `packet!` macro serializes a packet by writing each of its fields in order with this trait,
+/// so every field type used in a packet must implement it.
+///
+pub trait ProtocolField: Sized {
+    ///
+    /// Appends the wire representation of the value to `buffer`.
+    ///
+    fn write_to(&self, buffer: &mut Vec<u8>);
+
+    ///
+    /// Reads a value from the front of `buffer`, consuming its bytes.
+    /// Returns `None` if the buffer doesn't contain a valid value.
+    ///
+    fn read_from(buffer: &mut BytesMut) -> Option<Self>;
+
+    ///
+    /// Returns an estimate of how many bytes `write_to` appends, used to pre-size buffers.
+    /// Returns 0 if the size isn't known up front.
+    ///
+    fn size_hint(&self) -> usize {
+        0
+    }
+}
+
+///
+/// An `i32` encoded as a VarInt.
+///
+/// Use this for fields wiki.vg lists as `VarInt`; a plain integer type is encoded with its fixed size.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarInt(pub i32);
+
+impl ProtocolField for VarInt {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&PacketFormatter::write_varint(self.0 as u32 as usize));
+    }
+
+    fn read_from(buffer: &mut BytesMut) -> Option<Self> {
+        // Negative values use all 32 bits, so unlike `read_varint` the value isn't limited to i32::MAX.
+        let mut value: u32 = 0;
+        for count in 0..5 {
+            if !buffer.has_remaining() {
+                return None;
+            }
+
+            let byte = buffer.get_u8();
+            // The fifth byte only holds the top 4 bits, and no byte may follow it.
+            if count == 4 && byte > 0x0F {
+                return None;
+            }
+
+            value |= ((byte & 0x7F) as u32) << (7 * count);
+            if byte & 0x80 == 0 {
+                return Some(VarInt(value as i32));
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> usize {
+        5 // A VarInt is at most 5 bytes long.
+    }
+}
+
+///
+/// A block coordinate, packed into a single `i64` on the wire.
+///
+/// The layout is 26 bits of x, then 26 bits of z, then 12 bits of y, each as two's complement.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockPosition {
+    ///
+    /// Packs the position into the `i64` used by the protocol.
+    /// Coordinates outside of the packed range (±2^25 for x and z, ±2^11 for y) wrap around.
+    ///
+    pub fn to_i64(&self) -> i64 {
+        ((self.x as i64 & 0x3FF_FFFF) << 38) | ((self.z as i64 & 0x3FF_FFFF) << 12) | (self.y as i64 & 0xFFF)
+    }
+
+    ///
+    /// Unpacks a position from the `i64` used by the protocol.
+    ///
+    pub fn from_i64(value: i64) -> Self {
+        // Arithmetic shifts sign-extend each coordinate from the top of the i64.
+        Self {
+            x: (value >> 38) as i32,
+            y: (value << 52 >> 52) as i32,
+            z: (value << 26 >> 38) as i32,
+        }
+    }
+}
+
+impl ProtocolField for BlockPosition {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        self.to_i64().write_to(buffer);
+    }
+
+    fn read_from(buffer: &mut BytesMut) -> Option<Self> {
+        i64::read_from(buffer).map(Self::from_i64)
+    }
+
+    fn size_hint(&self) -> usize {
+        8
+    }
+}
+
+impl ProtocolField for String {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        serialize_string(self, buffer);
+    }
+
+    fn read_from(buffer: &mut BytesMut) -> Option<Self> {
+        deserialize_string(buffer)
+    }
+
+    fn size_hint(&self) -> usize {
+        5 + self.len()
+    }
+}
+
+impl ProtocolField for u8 {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        buffer.push(*self);
+    }
+
+    fn read_from(buffer: &mut BytesMut) -> Option<Self> {
+        if buffer.is_empty() {
+            return None;
+        }
+
+        Some(buffer.get_u8())
+    }
+
+    fn size_hint(&self) -> usize {
+        1
+    }
+}
+
+impl ProtocolField for u16 {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.to_be_bytes());
+    }
+
+    fn read_from(buffer: &mut BytesMut) -> Option<Self> {
+        if buffer.len() < 2 {
+            return None;
+        }
+
+        Some(buffer.get_u16())
+    }
+
+    fn size_hint(&self) -> usize {
+        2
+    }
+}
+
+impl ProtocolField for i64 {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.to_be_bytes());
+    }
+
+    fn read_from(buffer: &mut BytesMut) -> Option<Self> {
+        if buffer.len() < 8 {
+            return None;
+        }
+
+        Some(buffer.get_i64())
+    }
+
+    fn size_hint(&self) -> usize {
+        8
+    }
+}
+
+/// Writes a string as a VarInt byte length followed by its UTF-8 bytes.
+///
+/// # Arguments
+///
+/// * `value` - The string to write.
+/// * `buffer` - The buffer to append the encoded string to.
+pub fn serialize_string(value: &str, buffer: &mut Vec<u8>) {
+    buffer.extend_from_slice(&PacketFormatter::write_varint(value.len()));
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+/// Reads a VarInt-length-prefixed UTF-8 string from the front of the buffer.
+///
+/// # Arguments
+///
+/// * `buffer` - The buffer to read from.
+///
+/// # Returns
+///
+/// The decoded string, or `None` if the buffer is too short or the bytes aren't valid UTF-8.
+pub fn deserialize_string(buffer: &mut BytesMut) -> Option<String> {
+    let (length, _) = PacketFormatter::read_varint(buffer)?;
+    if buffer.len() < length {
+        return None;
+    }
+
+    String::from_utf8(buffer.split_to(length).to_vec()).ok()
+}
+
+/// Converts an angle in degrees to the protocol's angle byte, measured in 1/256 of a full turn.
+///
+/// The angle is wrapped into `[0, 360)` first, so negative angles and angles of a full turn or more
+/// are encoded like their equivalent in that range. The result is rounded to the nearest step.
+///
+/// # Arguments
+///
+/// * `degrees` - The angle in degrees, such as `Location.yaw`.
+pub fn angle_to_byte(degrees: f32) -> u8 {
+    let steps = (degrees.rem_euclid(360.0) / 360.0 * 256.0).round();
+
+    // Rounding up from just below 360 degrees lands on 256, which is a full turn again.
+    (steps as u32 % 256) as u8
+}
+
+/// Converts the protocol's angle byte back to an angle in degrees, in `[0, 360)`.
+///
+/// # Arguments
+///
+/// * `angle` - The angle in 1/256 of a full turn.
+pub fn byte_to_angle(angle: u8) -> f32 {
+    angle as f32 * 360.0 / 256.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_are_varint_length_prefixed() {
+        let mut buffer = Vec::new();
+        serialize_string("héllo", &mut buffer);
+
+        assert_eq!(buffer[0], 6);
+        assert_eq!(&buffer[1..], "héllo".as_bytes());
+        assert_eq!(
+            deserialize_string(&mut BytesMut::from(&buffer[..])),
+            Some("héllo".to_string())
+        );
+    }
+
+    #[test]
+    fn truncated_string_is_rejected() {
+        assert_eq!(deserialize_string(&mut BytesMut::from(&[0x05, b'a'][..])), None);
+    }
+
+    #[test]
+    fn negative_varints_round_trip() {
+        for value in [-1, i32::MIN, i32::MAX, 0, 300] {
+            let mut buffer = Vec::new();
+            VarInt(value).write_to(&mut buffer);
+
+            let mut bytes = BytesMut::from(&buffer[..]);
+            assert_eq!(VarInt::read_from(&mut bytes), Some(VarInt(value)));
+            assert!(bytes.is_empty());
+        }
+
+        let mut buffer = Vec::new();
+        VarInt(-1).write_to(&mut buffer);
+        assert_eq!(buffer, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    }
+
+    #[test]
+    fn varints_longer_than_32_bits_are_rejected() {
+        assert_eq!(VarInt::read_from(&mut BytesMut::from(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F][..])), None);
+        assert_eq!(VarInt::read_from(&mut BytesMut::from(&[0xFF, 0xFF, 0xFF, 0xFF, 0x8F, 0x01][..])), None);
+        assert_eq!(VarInt::read_from(&mut BytesMut::from(&[0x80][..])), None);
+    }
+
+    #[test]
+    fn u16_is_big_endian() {
+        let mut buffer = Vec::new();
+        25565u16.write_to(&mut buffer);
+
+        assert_eq!(buffer, vec![0x63, 0xDD]);
+        assert_eq!(u16::read_from(&mut BytesMut::from(&buffer[..])), Some(25565));
+    }
+
+    #[test]
+    fn block_positions_round_trip() {
+        let positions = [
+            BlockPosition { x: 0, y: 0, z: 0 },
+            BlockPosition { x: 18357644, y: 831, z: -20882616 },
+            BlockPosition { x: -1, y: -1, z: -1 },
+            BlockPosition { x: -33554432, y: -2048, z: -33554432 },
+            BlockPosition { x: 33554431, y: 2047, z: 33554431 },
+            BlockPosition { x: -7, y: -64, z: 12 },
+        ];
+
+        for position in positions {
+            assert_eq!(BlockPosition::from_i64(position.to_i64()), position);
+
+            let mut buffer = Vec::new();
+            position.write_to(&mut buffer);
+            assert_eq!(BlockPosition::read_from(&mut BytesMut::from(&buffer[..])), Some(position));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::unusual_byte_groupings)]
+    fn block_position_matches_the_wire_layout() {
+        // The example from wiki.vg, grouped as x, z and y.
+        let position = BlockPosition { x: 18357644, y: 831, z: -20882616 };
+        assert_eq!(position.to_i64() as u64, 0b01000110000001110110001100_10110000010101101101001000_001100111111);
+
+        assert_eq!(BlockPosition { x: -1, y: -1, z: -1 }.to_i64(), -1);
+        assert_eq!(BlockPosition::from_i64(-1), BlockPosition { x: -1, y: -1, z: -1 });
+    }
+
+    #[test]
+    fn angles_wrap_and_round_to_the_nearest_step() {
+        assert_eq!(angle_to_byte(0.0), 0);
+        assert_eq!(angle_to_byte(90.0), 64);
+        assert_eq!(angle_to_byte(180.0), 128);
+        assert_eq!(angle_to_byte(270.0), 192);
+        assert_eq!(angle_to_byte(360.0), 0);
+        assert_eq!(angle_to_byte(-90.0), 192);
+        assert_eq!(angle_to_byte(359.9), 0);
+        assert_eq!(angle_to_byte(1.0), 1); // 0.71 steps rounds up.
+
+        assert_eq!(byte_to_angle(0), 0.0);
+        assert_eq!(byte_to_angle(64), 90.0);
+        assert_eq!(byte_to_angle(192), 270.0);
+        assert_eq!(byte_to_angle(angle_to_byte(-90.0)), 270.0);
+    }
+}
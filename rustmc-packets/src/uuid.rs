@@ -0,0 +1,134 @@
+use std::{fmt, str::FromStr};
+
+use bytes::{Buf, BytesMut};
+use md5::{Digest, Md5};
+use rustmc_errors::UUIDError;
+
+use crate::types::ProtocolField;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct UUID {
+    pub data: [u8; 16],
+}
+
+impl UUID {
+    /// Computes the UUID an offline-mode server assigns to a username.
+    ///
+    /// This matches Java's `UUID.nameUUIDFromBytes("OfflinePlayer:<name>")`: a version 3 UUID built
+    /// from the MD5 hash of the name, with the version and variant bits set.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The username of the player.
+    pub fn from_username_offline(name: &str) -> Self {
+        let mut data: [u8; 16] = Md5::digest(format!("OfflinePlayer:{}", name)).into();
+        data[6] = (data[6] & 0x0F) | 0x30; // Version 3
+        data[8] = (data[8] & 0x3F) | 0x80; // IETF variant
+
+        Self { data }
+    }
+
+    /// Formats the UUID in its canonical hyphenated form, e.g. `b50ad385-829d-3141-a216-7e7d7539ba7f`.
+    pub fn to_hyphenated_string(&self) -> String {
+        let hex: String = self.data.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+}
+
+impl FromStr for UUID {
+    type Err = UUIDError;
+
+    /// Parses a UUID from its hyphenated form or the compact 32 hex digit form Mojang's API returns.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let hex = match value.len() {
+            32 => value.to_string(),
+            36 => {
+                if [8, 13, 18, 23].iter().any(|&index| value.as_bytes()[index] != b'-') {
+                    return Err(UUIDError::InvalidCharacter);
+                }
+
+                value.replace('-', "")
+            }
+            _ => return Err(UUIDError::InvalidLength),
+        };
+
+        if hex.len() != 32 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(UUIDError::InvalidCharacter);
+        }
+
+        let mut data = [0; 16];
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)
+                .map_err(|_| UUIDError::InvalidCharacter)?;
+        }
+
+        Ok(Self { data })
+    }
+}
+
+impl fmt::Display for UUID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_hyphenated_string())
+    }
+}
+
+impl ProtocolField for UUID {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.data);
+    }
+
+    fn read_from(buffer: &mut BytesMut) -> Option<Self> {
+        if buffer.len() < 16 {
+            return None;
+        }
+
+        let mut data = [0; 16];
+        buffer.copy_to_slice(&mut data);
+        Some(Self { data })
+    }
+
+    fn size_hint(&self) -> usize {
+        16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTCH: &str = "b50ad385-829d-3141-a216-7e7d7539ba7f";
+
+    #[test]
+    fn offline_uuid_matches_vanilla() {
+        assert_eq!(UUID::from_username_offline("Notch").to_string(), NOTCH);
+        assert_ne!(UUID::from_username_offline("Notch"), UUID::from_username_offline("jeb_"));
+    }
+
+    #[test]
+    fn parses_hyphenated_and_compact_forms() {
+        let hyphenated: UUID = NOTCH.parse().unwrap();
+        let compact: UUID = NOTCH.replace('-', "").parse().unwrap();
+
+        assert_eq!(hyphenated, compact);
+        assert_eq!(hyphenated.to_hyphenated_string(), NOTCH);
+        assert_eq!(NOTCH.to_uppercase().parse::<UUID>().unwrap(), hyphenated);
+    }
+
+    #[test]
+    fn rejects_malformed_uuids() {
+        assert_eq!("b50ad385".parse::<UUID>(), Err(UUIDError::InvalidLength));
+        assert_eq!(
+            "b50ad385x829d-3141-a216-7e7d7539ba7f".parse::<UUID>(),
+            Err(UUIDError::InvalidCharacter)
+        );
+        assert_eq!(
+            "g50ad385829d3141a2167e7d7539ba7f".parse::<UUID>(),
+            Err(UUIDError::InvalidCharacter)
+        );
+        assert_eq!(
+            "b50ad385-829d-3141-a216-7e7d7539ba-f".parse::<UUID>(),
+            Err(UUIDError::InvalidCharacter)
+        );
+    }
+}
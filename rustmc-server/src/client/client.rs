@@ -1,3 +1,5 @@
+use std::net::SocketAddr;
+
 use async_trait::async_trait;
 use rustmc_errors::ConnectionError;
 use rustmc_packets::Packet;
@@ -11,18 +13,19 @@ use super::uuid::UUID;
 #[async_trait]
 pub trait Client {
     
-    /// Creates a new client instance with the given connection, username, and UUID.
+    /// Creates a new client instance with the given connection, address, username, and UUID.
     ///
     /// # Arguments
     ///
     /// * `connection` - The TCP stream connection to the server.
+    /// * `address` - The remote address of the connection.
     /// * `username` - The username of the client.
     /// * `uuid` - The UUID of the client.
     ///
     /// # Returns
     ///
     /// A new instance of the client.
-    fn new(connection: TcpStream, username: &str, uuid: UUID) -> Self;
+    fn new(connection: TcpStream, address: SocketAddr, username: &str, uuid: UUID) -> Self;
 
     /// Connects the client to the specified Minecraft server.
     ///
@@ -35,12 +38,12 @@ pub trait Client {
     /// A `Result` indicating whether the connection was successful or an error occurred.
     async fn connect(&mut self, server: &mut MinecraftServer) -> Result<(), ConnectionError>;
 
-    /// Disconnects the client from the server.
+    /// Disconnects the client from the server, showing it the given message.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A `Result` indicating whether the disconnection was successful or an error occurred.
-    async fn disconnect(&self);
+    /// * `message` - The reason shown to the client.
+    async fn disconnect(&self, message: &str);
 
     /// Sends a packet to the server.
     ///
@@ -1,36 +1,65 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use rustmc_errors::PacketError;
-use rustmc_packets::Packet;
-use tokio::{sync::Mutex, net::TcpStream, io::AsyncWriteExt};
+use rustmc_packets::{
+    server::set_compression::SetCompressionPacket, types::VarInt, CompressionThreshold, Packet,
+    PacketFormatter,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::Mutex,
+};
 
 /// Represents a client connection to a server.
 #[derive(Debug, Clone)]
 pub struct ClientConnection {
-    pub connection: Arc<Mutex<TcpStream>>,
+    /// The read half of the TCP stream, used by the packet retriever.
+    pub reader: Arc<Mutex<OwnedReadHalf>>,
+
+    /// The write half of the TCP stream, used to send packets.
+    pub writer: Arc<Mutex<OwnedWriteHalf>>,
+
+    /// The remote address of the client, captured when the connection was accepted.
+    pub address: SocketAddr,
+
+    /// The compression threshold, `None` while the connection is uncompressed.
+    pub compression_threshold: CompressionThreshold,
 }
 
 impl ClientConnection {
     /// Creates a new `ClientConnection` instance.
     ///
+    /// The stream is split into its read and write halves so packets can be sent while another task
+    /// is waiting for incoming data.
+    ///
     /// # Arguments
     ///
     /// * `connection` - The TCP stream representing the connection.
+    /// * `address` - The remote address of the client, as returned by `accept`.
     ///
     /// # Returns
     ///
     /// A new `ClientConnection` instance.
-    pub fn new(connection: TcpStream) -> Self {
+    pub fn new(connection: TcpStream, address: SocketAddr) -> Self {
+        let (reader, writer) = connection.into_split();
+
         Self {
-            connection: Arc::new(Mutex::new(connection)),
+            reader: Arc::new(Mutex::new(reader)),
+            writer: Arc::new(Mutex::new(writer)),
+            address,
+            compression_threshold: CompressionThreshold::default(),
         }
     }
 
     /// Disconnects the client from the server.
     ///
-    /// This method asynchronously shuts down the TCP stream connection.
+    /// This method asynchronously shuts down the write half of the TCP stream.
     pub async fn disconnect(&self) {
-        self.connection.lock().await.shutdown();
+        let _ = self.writer.lock().await.shutdown().await;
     }
 
     /// Sends a packet to the server.
@@ -46,15 +75,67 @@ impl ClientConnection {
     where
         P: Packet
     {
-        let connection = self.connection.clone();
-        let data = packet.into_protocol_format();
-        println!("Sent Packet: {:?}", data.clone()); // TODO: DEBUG
+        let data = PacketFormatter::format_data(packet, *self.compression_threshold.lock().await);
+        log::trace!("Sent packet {:#04x}: {:?}", packet.id(), data);
+
+        let mut writer = self.writer.lock().await;
+
+        match writer.write_all(&data).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(PacketError::from(err)),
+        }
+    }
+
+    /// Sends several packets in a single write.
+    ///
+    /// Every packet is framed first and the frames are written together, so they arrive in order
+    /// and the socket is only locked once.
+    ///
+    /// # Arguments
+    ///
+    /// * `packets` - The packets to send, in order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or failure of sending the packets.
+    pub async fn send_packets(&self, packets: &[&dyn Packet]) -> Result<(), PacketError> {
+        let threshold = *self.compression_threshold.lock().await;
+        let data: Vec<u8> = packets
+            .iter()
+            .flat_map(|packet| PacketFormatter::format_data(*packet, threshold))
+            .collect();
+
+        let mut writer = self.writer.lock().await;
+
+        match writer.write_all(&data).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(PacketError::from(err)),
+        }
+    }
+
+    /// Enables compression for the connection.
+    ///
+    /// The `SetCompressionPacket` is sent uncompressed, and every packet sent or received after it
+    /// uses the compressed format. Packets of at least `threshold` bytes are zlib-compressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The size from which packets are compressed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or failure of sending the `SetCompressionPacket`.
+    pub async fn enable_compression(&self, threshold: usize) -> Result<(), PacketError> {
+        let packet = SetCompressionPacket { threshold: VarInt(threshold as i32) };
+        let data = PacketFormatter::format_data(&packet, None);
 
-        let mut connection = connection.lock().await;
+        // Hold the writer so no other packet is sent between the switch and the SetCompression packet.
+        let mut writer = self.writer.lock().await;
+        *self.compression_threshold.lock().await = Some(threshold);
 
-        match connection.write_all(&data).await {
+        match writer.write_all(&data).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(PacketError::ErrorSendingPacket),
+            Err(err) => Err(PacketError::from(err)),
         }
     }
-}
\ No newline at end of file
+}
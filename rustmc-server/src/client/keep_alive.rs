@@ -0,0 +1,155 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How often a keep-alive is sent to a player in the play state.
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How many keep-alives may go unanswered before the player is disconnected.
+pub const MAX_MISSED_KEEP_ALIVES: u32 = 2;
+
+/// Tracks the keep-alives sent to a player and the answers to them.
+#[derive(Debug, Clone, Default)]
+pub struct KeepAlive {
+    /// The ID of the last keep-alive sent, until the player answers it.
+    pub pending_id: Option<i64>,
+
+    /// When the pending keep-alive was sent.
+    pub sent_at: Option<Instant>,
+
+    /// How many keep-alives in a row went unanswered.
+    pub missed: u32,
+}
+
+impl KeepAlive {
+    /// Records that a keep-alive with the given ID is being sent.
+    ///
+    /// If the previous keep-alive is still pending, it counts as missed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the keep-alive.
+    /// * `now` - The time the keep-alive is sent.
+    ///
+    /// # Returns
+    ///
+    /// `true` if too many keep-alives went unanswered and the player should be disconnected.
+    pub fn send(&mut self, id: i64, now: Instant) -> bool {
+        if self.pending_id.is_some() {
+            self.missed += 1;
+        }
+
+        self.pending_id = Some(id);
+        self.sent_at = Some(now);
+
+        self.missed >= MAX_MISSED_KEEP_ALIVES
+    }
+
+    /// Records the player's answer to a keep-alive.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID the player answered with.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the ID matches the pending keep-alive, `false` if it doesn't match or nothing is pending.
+    pub fn acknowledge(&mut self, id: i64) -> bool {
+        if self.pending_id != Some(id) {
+            return false;
+        }
+
+        self.pending_id = None;
+        self.missed = 0;
+        true
+    }
+
+    /// Describes a keep-alive answer that didn't match the pending keep-alive.
+    ///
+    /// # Arguments
+    ///
+    /// * `received` - The ID the player answered with.
+    /// * `now` - The time the answer arrived.
+    pub fn mismatch(&self, received: i64, now: Instant) -> KeepAliveMismatch {
+        KeepAliveMismatch {
+            expected: self.pending_id,
+            received,
+            since_sent: self.sent_at.map(|sent_at| now.saturating_duration_since(sent_at)),
+        }
+    }
+}
+
+/// Diagnostics for a keep-alive answer with the wrong ID, usually caused by a buggy client or proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAliveMismatch {
+    /// The ID of the pending keep-alive, if any was pending.
+    pub expected: Option<i64>,
+
+    /// The ID the player answered with.
+    pub received: i64,
+
+    /// How long ago the last keep-alive was sent, if any was sent.
+    pub since_sent: Option<Duration>,
+}
+
+impl fmt::Display for KeepAliveMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expected {
+            Some(expected) => write!(f, "expected keep-alive {}, received {}", expected, self.received)?,
+            None => write!(f, "expected no keep-alive, received {}", self.received)?,
+        }
+
+        match self.since_sent {
+            Some(since_sent) => write!(f, " ({:?} after sending)", since_sent),
+            None => write!(f, " (none sent yet)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mismatched_ack_is_detected() {
+        let mut keep_alive = KeepAlive::default();
+        keep_alive.send(1, Instant::now());
+
+        assert!(!keep_alive.acknowledge(2));
+        assert_eq!(keep_alive.pending_id, Some(1));
+        assert!(keep_alive.acknowledge(1));
+        assert!(!keep_alive.acknowledge(1));
+    }
+
+    #[test]
+    fn mismatch_reports_both_ids() {
+        let sent_at = Instant::now();
+        let mut keep_alive = KeepAlive::default();
+        keep_alive.send(17, sent_at);
+
+        assert!(!keep_alive.acknowledge(42));
+        let mismatch = keep_alive.mismatch(42, sent_at + Duration::from_millis(250));
+        assert_eq!(mismatch.expected, Some(17));
+        assert_eq!(mismatch.since_sent, Some(Duration::from_millis(250)));
+
+        let message = mismatch.to_string();
+        assert!(message.contains("17"), "{}", message);
+        assert!(message.contains("42"), "{}", message);
+        assert_eq!(
+            KeepAlive::default().mismatch(5, Instant::now()).to_string(),
+            "expected no keep-alive, received 5 (none sent yet)"
+        );
+    }
+
+    #[test]
+    fn two_missed_keep_alives_time_out() {
+        let mut keep_alive = KeepAlive::default();
+
+        assert!(!keep_alive.send(1, Instant::now()));
+        assert!(!keep_alive.send(2, Instant::now()));
+        assert!(keep_alive.send(3, Instant::now()));
+
+        // Answering resets the count.
+        assert!(keep_alive.acknowledge(3));
+        assert!(!keep_alive.send(4, Instant::now()));
+    }
+}
@@ -1,11 +1,22 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
 use async_trait::async_trait;
-use rustmc_errors::ConnectionError;
-use rustmc_packets::{server::handshake::HandshakePacket, Packet};
+use rustmc_errors::{ConnectionError, PacketError};
+use rustmc_packets::{
+    server::{
+        configuration_disconnect::ConfigurationDisconnectPacket, disconnect::DisconnectPacket,
+        login_disconnect::LoginDisconnectPacket,
+    },
+    ConnectionState, Packet,
+};
 use tokio::net::TcpStream;
 
 use crate::MinecraftServer;
 
-use self::{client::Client, uuid::UUID, connection::ClientConnection};
+use self::{client::Client, uuid::UUID, connection::ClientConnection, keep_alive::KeepAlive};
 
 /// Represents a player in the game.
 #[derive(Debug, Clone)]
@@ -18,6 +29,52 @@ pub struct Player {
 
     /// The UUID (Universally Unique Identifier) of the player.
     pub uuid: UUID,
+
+    /// The keep-alive state of the player, shared by every clone of the player.
+    pub keep_alive: Arc<Mutex<KeepAlive>>,
+
+    /// The state of the player's connection, shared by every clone of the player.
+    pub state: Arc<Mutex<ConnectionState>>,
+
+    /// Whether Login Success was sent, so the client may acknowledge it but not log in again.
+    pub login_success_sent: bool,
+}
+
+impl Player {
+    /// Returns the remote address of the player.
+    ///
+    /// The address is captured when the connection is accepted, so it stays available after the
+    /// socket has been shut down.
+    pub fn address(&self) -> SocketAddr {
+        self.connection.address
+    }
+
+    /// Returns the state of the player's connection.
+    pub fn state(&self) -> ConnectionState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Moves the player's connection to the given state.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The new state of the connection.
+    pub fn set_state(&self, state: ConnectionState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    /// Sends several packets to the player in one batched write.
+    ///
+    /// # Arguments
+    ///
+    /// * `packets` - The packets to send, in order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating whether the packets were sent successfully or an error occurred.
+    pub async fn send_packets(&mut self, packets: &[&dyn Packet]) -> Result<(), PacketError> {
+        self.connection.send_packets(packets).await
+    }
 }
 
 #[async_trait]
@@ -27,72 +84,80 @@ impl Client for Player {
     /// # Arguments
     ///
     /// * `connection` - The TCP stream connection for the player.
+    /// * `address` - The remote address of the player's connection.
     /// * `username` - The username of the player.
     /// * `uuid` - The UUID of the player.
     ///
     /// # Returns
     ///
     /// A new instance of the `Player` struct.
-    fn new(connection: TcpStream, username: &str, uuid: UUID) -> Self {
+    fn new(connection: TcpStream, address: SocketAddr, username: &str, uuid: UUID) -> Self {
         Self {
-            connection: ClientConnection::new(connection),
+            connection: ClientConnection::new(connection, address),
             username: username.to_string(),
             uuid,
+            keep_alive: Arc::new(Mutex::new(KeepAlive::default())),
+            state: Arc::new(Mutex::new(ConnectionState::Handshaking)),
+            login_success_sent: false,
         }
     }
 
     /// Connects the player to the Minecraft server.
     ///
+    /// The player is added to the server's player list. Nothing is written to the connection:
+    /// the client drives the handshake, and the server only answers it in `handle_connection`.
+    ///
     /// # Arguments
     ///
     /// * `server` - A mutable reference to the Minecraft server.
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if the connection is successful, otherwise returns a `ConnectionError`.
+    /// Returns `Ok(())` once the player is registered.
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// # use crate::MinecraftServer;
-    /// # use crate::ConnectionError;
-    /// # use crate::HandshakePacket;
-    /// # struct Player {
-    /// #     username: String,
-    /// # }
-    /// # impl Player {
-    /// #     async fn send_packet(&mut self, packet: &HandshakePacket) {}
-    /// # }
-    /// # async fn example() -> Result<(), ConnectionError> {
-    /// #     let mut server = MinecraftServer::new();
-    /// #     let mut player = Player {
-    /// #         username: String::from("player1"),
-    /// #     };
-    ///     player.connect(&mut server)?;
-    ///     Ok(())
-    /// # }
+    /// ```ignore
+    /// let mut player = Player::new(stream, address, "", UUID { data: [0; 16] });
+    /// player.connect(&mut server).await?;
     /// ```
     async fn connect(&mut self, server: &mut MinecraftServer) -> Result<(), ConnectionError> {
-        println!("Player {} connected to server.", self.username);
-
-        self.send_packet(&HandshakePacket {
-            protocol_version: 764, // 1.20.2
-            server_address: server.address.clone(),
-            server_port: server.port.clone(),
-            next_state: 0x02, // Login
-        })
-        .await;
+        server.players.lock().await.push(self.clone());
 
         Ok(())
     }
 
     /// Disconnects the player from the server.
     ///
-    /// # Returns
+    /// The packet carrying the message depends on the state of the connection: a
+    /// `LoginDisconnectPacket` during login, a `ConfigurationDisconnectPacket` during configuration
+    /// and a `DisconnectPacket` during play. Before login the client has no disconnect packet, so
+    /// the connection is only closed.
+    /// The connection is shut down even if the packet could not be sent.
+    ///
+    /// # Arguments
     ///
-    /// Returns `Ok(())` if the disconnection was successful.
-    /// Returns an `Err` variant if there was an error during the disconnection process.
-    async fn disconnect(&self) {
+    /// * `message` - The reason shown on the player's disconnect screen.
+    async fn disconnect(&self, message: &str) {
+        let sent = match self.state() {
+            ConnectionState::Handshaking | ConnectionState::Status => Ok(()),
+            ConnectionState::Login => {
+                self.connection
+                    .send_packet(&LoginDisconnectPacket::from_message(message))
+                    .await
+            }
+            ConnectionState::Configuration => {
+                self.connection
+                    .send_packet(&ConfigurationDisconnectPacket::from_message(message))
+                    .await
+            }
+            ConnectionState::Play => self.connection.send_packet(&DisconnectPacket::from_message(message)).await,
+        };
+
+        if let Err(err) = sent {
+            log::warn!("Error sending packet: {}", err);
+        }
+
         self.connection.disconnect().await;
     }
 
@@ -108,7 +173,7 @@ impl Client for Player {
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```ignore
     /// use crate::players::Player;
     /// use crate::network::Packet;
     ///
@@ -118,7 +183,7 @@ impl Client for Player {
     ///
     /// match result {
     ///     Ok(()) => println!("Packet sent successfully"),
-    ///     Err(err) => println!("Error sending packet: {:?}", err),
+    ///     Err(err) => println!("Error sending packet: {}", err),
     /// }
     /// ```
     async fn send_packet<P>(&mut self, packet: &P)
@@ -129,11 +194,205 @@ impl Client for Player {
 
         match packet_result {
             Ok(_) => {},
-            Err(err) => println!("Error sending packet: {:?}", err),
+            Err(err) => log::warn!("Error sending packet: {}", err),
         }
     }
 }
 
+#[allow(clippy::module_inception)]
 pub mod client;
 pub mod uuid;
 pub mod connection;
+pub mod keep_alive;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustmc_packets::server::{ping::PingPacket, pong::PongPacket};
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    /// Returns a player whose client has reset the connection.
+    async fn player_with_reset_client() -> Player {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, address) = listener.accept().await.unwrap();
+
+        // Closing a socket that lingers for zero seconds sends a reset instead of a FIN.
+        socket2::SockRef::from(&client)
+            .set_linger(Some(std::time::Duration::ZERO))
+            .unwrap();
+        drop(client);
+
+        Player::new(stream, address, "Notch", UUID { data: [0; 16] })
+    }
+
+    #[tokio::test]
+    async fn writing_to_a_closed_connection_returns_the_io_error() {
+        let player = player_with_reset_client().await;
+
+        // Writes are buffered until the reset arrives, so the first ones may still succeed.
+        for _ in 0..100 {
+            if let Err(err) = player.connection.send_packet(&PongPacket { payload: 1 }).await {
+                assert!(
+                    matches!(
+                        err,
+                        PacketError::Io(std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset)
+                    ),
+                    "unexpected error: {:?}",
+                    err
+                );
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("writing to a closed connection never failed");
+    }
+
+    #[tokio::test]
+    async fn reading_from_a_reset_connection_returns_the_io_error() {
+        use rustmc_packets::PacketRetriever;
+        use tokio::sync::mpsc;
+
+        let player = player_with_reset_client().await;
+        let (sender, _frames) = mpsc::channel(8);
+
+        let result = PacketRetriever
+            .retrieve_packets(&mut *player.connection.reader.lock().await, Default::default(), sender)
+            .await;
+        assert!(matches!(result, Err(PacketError::Io(std::io::ErrorKind::ConnectionReset))));
+    }
+
+    #[tokio::test]
+    async fn address_survives_disconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, address) = listener.accept().await.unwrap();
+
+        let player = Player::new(stream, address, "Notch", UUID { data: [0; 16] });
+        player.disconnect("Bye").await;
+
+        assert_eq!(player.address(), client.local_addr().unwrap());
+    }
+
+    #[tokio::test]
+    async fn send_packets_writes_frames_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, address) = listener.accept().await.unwrap();
+        let mut player = Player::new(stream, address, "Notch", UUID { data: [0; 16] });
+
+        let first = PingPacket { payload: 1 };
+        let second = PongPacket { payload: 2 };
+        let third = PingPacket { payload: 3 };
+        player.send_packets(&[&first, &second, &third]).await.unwrap();
+        player.connection.disconnect().await;
+
+        let mut expected = first.into_protocol_format();
+        expected.extend(second.into_protocol_format());
+        expected.extend(third.into_protocol_format());
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn compression_applies_after_set_compression() {
+        use rustmc_packets::{server::status_response::StatusResponsePacket, PacketRetriever};
+        use std::sync::Arc;
+        use tokio::sync::{mpsc, Mutex};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, address) = listener.accept().await.unwrap();
+        let player = Player::new(stream, address, "Notch", UUID { data: [0; 16] });
+
+        player.connection.enable_compression(256).await.unwrap();
+
+        // SetCompression itself is sent in the uncompressed format.
+        let mut set_compression = [0; 4];
+        client.read_exact(&mut set_compression).await.unwrap();
+        assert_eq!(set_compression, [0x03, 0x03, 0x80, 0x02]);
+
+        let small = PingPacket { payload: 7 };
+        let large = StatusResponsePacket { json_response: "x".repeat(1024) };
+        player.connection.send_packets(&[&small, &large]).await.unwrap();
+        player.connection.disconnect().await;
+
+        let (sender, mut frames) = mpsc::channel(8);
+        PacketRetriever
+            .retrieve_packets(&mut client, Arc::new(Mutex::new(Some(256))), sender)
+            .await
+            .unwrap();
+
+        let (id, data) = frames.recv().await.unwrap();
+        assert_eq!((id, data.to_vec()), (0x01, small.serialize()));
+        let (id, data) = frames.recv().await.unwrap();
+        assert_eq!((id, data.to_vec()), (0x00, large.serialize()));
+    }
+
+    /// Disconnects a player in the given state and returns everything its client received.
+    async fn received_on_disconnect(state: ConnectionState) -> Vec<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, address) = listener.accept().await.unwrap();
+        let player = Player::new(stream, address, "Notch", UUID { data: [0; 16] });
+        player.set_state(state);
+
+        player.disconnect("Server is shutting down.").await;
+
+        // `read_to_end` only returns once the server has shut down its side.
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        received
+    }
+
+    #[tokio::test]
+    async fn disconnect_sends_the_reason_then_closes() {
+        assert_eq!(
+            received_on_disconnect(ConnectionState::Play).await,
+            DisconnectPacket::from_message("Server is shutting down.").into_protocol_format()
+        );
+    }
+
+    #[tokio::test]
+    async fn disconnect_during_login_sends_a_login_disconnect() {
+        assert_eq!(
+            received_on_disconnect(ConnectionState::Login).await,
+            LoginDisconnectPacket::from_message("Server is shutting down.").into_protocol_format()
+        );
+    }
+
+    #[tokio::test]
+    async fn disconnect_during_configuration_sends_a_configuration_disconnect() {
+        assert_eq!(
+            received_on_disconnect(ConnectionState::Configuration).await,
+            ConfigurationDisconnectPacket::from_message("Server is shutting down.").into_protocol_format()
+        );
+    }
+
+    #[tokio::test]
+    async fn disconnect_before_login_only_closes() {
+        assert_eq!(received_on_disconnect(ConnectionState::Status).await, Vec::<u8>::new());
+        assert_eq!(received_on_disconnect(ConnectionState::Handshaking).await, Vec::<u8>::new());
+    }
+
+    #[tokio::test]
+    async fn connect_registers_without_writing() {
+        use crate::tickable_server::TickableServer;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, address) = listener.accept().await.unwrap();
+        let mut player = Player::new(stream, address, "Notch", UUID { data: [0; 16] });
+        let mut server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+
+        player.connect(&mut server).await.unwrap();
+        player.connection.disconnect().await;
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert!(received.is_empty());
+        assert_eq!(server.get_players().await.len(), 1);
+    }
+}
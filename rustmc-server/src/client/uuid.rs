@@ -1,4 +1 @@
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-pub struct UUID {
-    pub data: [u8; 16],
-}
\ No newline at end of file
+pub use rustmc_packets::uuid::UUID;
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use crate::{
+    client::keep_alive::KEEP_ALIVE_INTERVAL,
+    status::{MAX_PLAYERS, MOTD},
+    BIND_ATTEMPTS, BIND_RETRY_DELAY,
+};
+
+/// The settings a `MinecraftServer` is created with.
+///
+/// Use struct update syntax to change only some settings:
+///
+/// ```ignore
+/// let server = MinecraftServer::with_config(ServerConfig {
+///     motd: "My Server".to_string(),
+///     ..ServerConfig::default()
+/// });
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    /// The IP address or hostname to bind the server to.
+    pub address: String,
+    /// The port number to bind the server to.
+    pub port: u16,
+    /// How many times binding the listener is attempted before `start` gives up.
+    pub bind_attempts: u32,
+    /// The delay before the first bind retry, doubled after every failed attempt.
+    pub bind_retry_delay: Duration,
+    /// Whether Nagle's algorithm is disabled on accepted connections, so small packets are sent
+    /// straight away.
+    pub tcp_nodelay: bool,
+
+    /// The message of the day shown under the server name in the multiplayer list.
+    pub motd: String,
+    /// The maximum number of players advertised in the multiplayer list.
+    pub max_players: usize,
+
+    /// Whether players are authenticated with Mojang. Only offline mode is implemented so far,
+    /// so players get offline UUIDs either way.
+    pub online_mode: bool,
+    /// How many chunks around a player are sent to them.
+    pub view_distance: u8,
+    /// How often a keep-alive is sent to a player in the play state.
+    pub keep_alive_interval: Duration,
+
+    /// The size from which packets are compressed after login, or `None` to never compress.
+    pub compression_threshold: Option<usize>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1".to_string(),
+            port: 25565,
+            bind_attempts: BIND_ATTEMPTS,
+            bind_retry_delay: BIND_RETRY_DELAY,
+            tcp_nodelay: true,
+            motd: MOTD.to_string(),
+            max_players: MAX_PLAYERS,
+            online_mode: false,
+            view_distance: 10,
+            keep_alive_interval: KEEP_ALIVE_INTERVAL,
+            compression_threshold: None,
+        }
+    }
+}
@@ -1,27 +1,206 @@
 use std::{
-    cell::{Ref, RefCell},
-    process,
-    sync::Arc,
-    time::Instant,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
-use client::{client::Client, uuid::UUID, Player, connection::ClientConnection};
+use bytes::BytesMut;
+use config::ServerConfig;
+use client::{
+    client::Client,
+    keep_alive::MAX_MISSED_KEEP_ALIVES,
+    uuid::UUID,
+    Player,
+};
+use lifecycle::{Lifecycle, LifecycleState};
 use rustmc_errors::PacketError;
-use rustmc_packets::{Packet, PacketRetriever};
+use rustmc_packets::{
+    client::converter::PacketByteConverter,
+    server::{
+        acknowledge_finish_configuration::AcknowledgeFinishConfigurationPacket,
+        finish_configuration::FinishConfigurationPacket, handshake::HandshakePacket,
+        keep_alive_client::KeepAliveClientPacket, keep_alive_server::KeepAliveServerPacket,
+        login_acknowledged::LoginAcknowledgedPacket, login_start::LoginStartPacket,
+        login_success::LoginSuccessPacket, ping::PingPacket, pong::PongPacket,
+    },
+    types::VarInt,
+    ConnectionState, Packet, PacketRetriever,
+};
+use socket2::{Domain, Protocol, Socket, Type};
 use tickable_server::TickableServer;
-use tokio::{net::TcpListener, sync::Mutex};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+/// The message shown to players when the server stops.
+pub const SHUTDOWN_MESSAGE: &str = "Server is shutting down.";
+
+/// The default number of times binding the listener is attempted before `start` gives up.
+pub const BIND_ATTEMPTS: u32 = 3;
+
+/// The default delay before the first bind retry, doubled after every failed attempt.
+pub const BIND_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a new connection may stay silent before it is dropped as a health check.
+pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Represents a Minecraft server.
 #[derive(Clone)]
 pub struct MinecraftServer {
-    /// The address of the server.
-    pub address: String,
-    /// The port number of the server.
-    pub port: u16,
+    /// The settings the server was created with.
+    pub config: ServerConfig,
+
+    /// The list of players currently connected to the server, shared by every connection task.
+    pub players: Arc<tokio::sync::Mutex<Vec<Player>>>,
+
+    /// The registry of packets the server can receive.
+    pub converter: PacketByteConverter,
+
+    /// Hooks registered by embedders, run when the server stops gracefully.
+    shutdown_hooks: Arc<Mutex<Vec<ShutdownHook>>>,
+
+    /// Whether the server is starting, running or stopping.
+    lifecycle: Lifecycle,
+
+    /// The signals of the running accept loop, replaced by every call to `start`.
+    accept_loop: Arc<Mutex<Option<AcceptLoopSignals>>>,
+}
+
+/// The signals shared with one run of the accept loop spawned by `start`.
+struct AcceptLoopSignals {
+    /// Tells the accept loop to return.
+    stop: oneshot::Sender<()>,
+    /// Closed by the accept loop once it has dropped the listener.
+    stopped: oneshot::Receiver<()>,
+}
+
+/// A cleanup callback run once when the server shuts down.
+pub type ShutdownHook = Box<dyn FnOnce() + Send>;
+
+impl MinecraftServer {
+    /// Creates a server with the given settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The address, port, status and compression settings of the server.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of the MinecraftServer struct.
+    pub fn with_config(config: ServerConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            players: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            converter: PacketByteConverter::new(),
+            shutdown_hooks: Arc::new(Mutex::new(Vec::new())),
+            lifecycle: Lifecycle::new(),
+            accept_loop: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Registers a hook to run when the server stops gracefully.
+    ///
+    /// Hooks run in reverse registration order (last registered runs first),
+    /// once every player is disconnected. They are not run by `force_stop`.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The cleanup callback to run on shutdown.
+    pub fn on_shutdown(&self, hook: ShutdownHook) {
+        self.shutdown_hooks.lock().unwrap().push(hook);
+    }
+
+    /// Broadcasts a packet to all players in the play state and counts the successful sends.
+    ///
+    /// Like `broadcast_packet`, a failed send doesn't stop the broadcast.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - The packet to broadcast.
+    ///
+    /// # Returns
+    ///
+    /// The number of players the packet was sent to.
+    pub async fn broadcast_packet_count<P>(&self, packet: &P) -> usize
+    where
+        P: Packet + Sync,
+    {
+        let (sent, _) = self.send_to_all(packet).await;
+        sent
+    }
+
+    /// Sends a packet to every player in the play state, continuing past failed sends.
+    ///
+    /// Connections that are still pinging or logging in are skipped, since the packet would mean
+    /// something else in their state.
+    ///
+    /// Returns the number of successful sends and the first error, if any.
+    async fn send_to_all<P>(&self, packet: &P) -> (usize, Result<(), PacketError>)
+    where
+        P: Packet + Sync,
+    {
+        let mut sent = 0;
+        let mut result = Ok(());
+
+        let players = self.get_players().await;
+        for player in players.iter().filter(|player| player.state() == ConnectionState::Play) {
+            match player.connection.send_packet(packet).await {
+                Ok(_) => sent += 1,
+                Err(err) => {
+                    log::warn!("Error broadcasting packet to {}: {}", player.address(), err);
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
 
-    /// The list of players currently connected to the server.
-    pub players: RefCell<Vec<Player>>,
+        (sent, result)
+    }
+
+    /// Returns the lifecycle state of the server.
+    ///
+    /// Embedders can wait for `LifecycleState::Running` before connecting to the server.
+    pub fn status(&self) -> LifecycleState {
+        self.lifecycle.get()
+    }
+
+    /// Resolves the server's address and binds the listener, retrying failed binds.
+    async fn bind(&self) -> io::Result<std::net::TcpListener> {
+        let address = (self.config.address.as_str(), self.config.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "address did not resolve"))?;
+
+        bind_with_retry(self.config.bind_attempts, self.config.bind_retry_delay, || bind_listener(address)).await
+    }
+
+    /// Stops the running accept loop, if any, and waits until it has released the listener.
+    async fn stop_accepting(&self) {
+        let signals = self.accept_loop.lock().unwrap().take();
+        if let Some(AcceptLoopSignals { stop, stopped }) = signals {
+            let _ = stop.send(());
+            // The loop drops its end once the listener is gone, so this never returns early.
+            let _ = stopped.await;
+        }
+    }
+
+    /// Runs and drains every registered shutdown hook in LIFO order.
+    ///
+    /// Each hook is removed before it runs, so calling this twice never runs a hook twice.
+    fn run_shutdown_hooks(&self) {
+        loop {
+            let hook = self.shutdown_hooks.lock().unwrap().pop();
+            match hook {
+                Some(hook) => hook(),
+                None => break,
+            }
+        }
+    }
 }
 
 ///
@@ -37,7 +216,8 @@ pub struct MinecraftServer {
 ///
 #[async_trait]
 impl TickableServer for MinecraftServer {
-    /// Creates a new instance of the MinecraftServer struct.
+    /// Creates a new instance of the MinecraftServer struct, using the default `ServerConfig` for
+    /// everything but the address and port.
     ///
     /// # Arguments
     ///
@@ -49,10 +229,10 @@ impl TickableServer for MinecraftServer {
     /// A new instance of the MinecraftServer struct.
     ///
     fn new(address: &str, port: u16) -> Arc<Self> {
-        Arc::new(Self {
+        Self::with_config(ServerConfig {
             address: address.to_string(),
             port,
-            players: RefCell::new(Vec::new()),
+            ..ServerConfig::default()
         })
     }
 
@@ -62,124 +242,140 @@ impl TickableServer for MinecraftServer {
     /// incoming connections. For each accepted connection, a new task is spawned to handle the
     /// connection using the `handle_connection` function.
     ///
+    /// The bind and the registration of the listener with the runtime happen before the accept loop
+    /// is spawned, so a failure such as the port already being in use is returned to the caller
+    /// instead of being lost in the task.
+    /// The listener uses `SO_REUSEADDR` so a restart can rebind while the old socket lingers in
+    /// `TIME_WAIT`, and a failed bind is retried `config.bind_attempts` times with a growing delay,
+    /// starting at `config.bind_retry_delay`.
+    ///
     /// # Arguments
     ///
     /// * `self` - The server instance.
     ///
+    /// The accept loop runs until `stop` or `force_stop` is called, then drops the listener so the
+    /// port can be bound again.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the accept loop once the listener is bound, or the `io::Error` from the last
+    /// failed bind. Awaiting the handle waits until the listener is released. A server that isn't
+    /// `Stopped` is left alone and an `AlreadyExists` error is returned.
+    ///
     /// # Example
     ///
-    /// ```rust
+    /// ```ignore
     /// let server = MinecraftServer::new("127.0.0.1", 8080);
-    /// server.start();
+    /// let accept_loop = server.start().await?;
+    /// server.stop().await;
+    /// accept_loop.await?;
     /// ```
-    fn start(&self) {
+    async fn start(&self) -> io::Result<JoinHandle<()>> {
         let start = Instant::now();
         let server = self.clone();
 
-        println!("Starting RustMC Server.");
-        tokio::spawn(async move {
-            let listener = TcpListener::bind(format!("{}:{}", server.address, server.port)).await;
-
-            match listener {
-                Ok(listener) => loop {
-                    match listener.accept().await {
-                        Ok((stream, _)) => {
-                            let mut server_clone = server.clone();
-                            let mut player = Player {
-                                connection: ClientConnection::new(stream),
-                                username: "wowie".into(),
-                                uuid: UUID { data: [0; 16] },
-                            };
-
-                            server.players.borrow_mut().push(player.clone());
-                            tokio::spawn(async move {
-                                handle_connection(&mut player, &mut server_clone).await;
-                            });
-                        }
-                        Err(e) => {
-                            eprintln!("Error: {}", e);
+        if !self.lifecycle.transition(LifecycleState::Stopped, LifecycleState::Starting) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "the server is already running"));
+        }
+        log::info!("Starting RustMC Server.");
+        let listener = match self.bind().await.and_then(TcpListener::from_std) {
+            Ok(listener) => listener,
+            Err(err) => {
+                self.lifecycle.set(LifecycleState::Stopped);
+                return Err(err);
+            }
+        };
+        self.lifecycle.set(LifecycleState::Running);
+
+        let (stop, mut stop_received) = oneshot::channel();
+        let (stopped_sender, stopped) = oneshot::channel::<()>();
+        *self.accept_loop.lock().unwrap() = Some(AcceptLoopSignals { stop, stopped });
+        let accept_loop = tokio::spawn(async move {
+            loop {
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = &mut stop_received => break,
+                };
+
+                match accepted {
+                    Ok((stream, address)) => {
+                        if let Err(e) = configure_stream(&stream, &server.config) {
+                            log::warn!("Error configuring connection from {}: {}", address, e);
                         }
+
+                        let mut server_clone = server.clone();
+                        tokio::spawn(async move {
+                            if is_health_check(&stream, HEALTH_CHECK_TIMEOUT).await {
+                                return;
+                            }
+
+                            let mut player = Player::new(stream, address, "", UUID { data: [0; 16] });
+
+                            if let Err(err) = player.connect(&mut server_clone).await {
+                                log::error!("Error connecting {}: {}", address, err);
+                                return;
+                            }
+                            handle_connection(&mut player, &mut server_clone).await;
+                            server_clone.players.lock().await.retain(|player| player.address() != address);
+                        });
+                    }
+                    Err(e) => {
+                        log::error!("Error accepting a connection: {}", e);
                     }
-                },
-                Err(e) => {
-                    eprintln!("Error while trying to start RustMC Server: {}", e);
-                    return;
                 }
             }
+
+            drop(listener);
+            drop(stopped_sender);
         });
 
-        println!("Started RustMC Server in {:?}", start.elapsed());
+        log::info!("Started RustMC Server in {:?}", start.elapsed());
+        Ok(accept_loop)
     }
 
     /// Stops the server by closing the TCP listener and disconnecting all players.
     ///
+    /// The accept loop is stopped and has released the listener before every player is sent a
+    /// disconnect message and dropped, then the shutdown hooks run. The process keeps running, and
+    /// the server can be started again.
+    ///
     /// # Examples
     ///
+    /// ```ignore
+    /// let accept_loop = server.start().await?;
+    /// tokio::signal::ctrl_c().await?;
+    ///
+    /// server.stop().await;
+    /// accept_loop.await?;
     /// ```
-    /// use std::process;
-    /// use tokio::net::TcpListener;
-    ///
-    /// # struct Player {
-    /// #     // ...
-    /// # }
-    /// #
-    /// # impl Player {
-    /// #     fn disconnect(&self, message: &str) {
-    /// #         // ...
-    /// #     }
-    /// # }
-    /// #
-    /// # struct Server {
-    /// #     address: String,
-    /// #     port: u16,
-    /// # }
-    /// #
-    /// # impl Server {
-    /// #     fn get_players(&self) -> Vec<Player> {
-    /// #         // ...
-    /// #     }
-    /// #
-    /// #     fn force_stop(&self) {
-    /// #         // ...
-    /// #     }
-    /// #
-    ///     pub async fn stop(&self) {
-    ///         let listener = TcpListener::bind(format!("{}:{}", self.address, self.port)).await;
-    ///
-    ///         match listener {
-    ///             Ok(listener) => {
-    ///
-    ///                 for player in self.get_players().iter() {
-    ///                     player.disconnect("Server is shutting down.");
-    ///                 }
-    ///
-    ///                 process::exit(0);
-    ///             }
-    ///             Err(e) => {
-    ///                 eprintln!("There was an error while trying to stop the server: {}", e);
-    ///                 eprintln!("Force stopping the server...");
-    ///                 self.force_stop();
-    ///             }
-    ///         }
-    ///     }
-    /// # }
-    /// ```
-    fn stop(&self) {
-        for player in self.get_players().iter() {
-            player.disconnect();
-        }
+    async fn stop(&self) {
+        self.lifecycle.set(LifecycleState::Stopping);
+        self.stop_accepting().await;
 
-        process::exit(0);
+        disconnect_players(self.get_players().await, SHUTDOWN_MESSAGE).await;
+        self.run_shutdown_hooks();
+
+        self.lifecycle.set(LifecycleState::Stopped);
     }
 
-    /// Forces the server to stop by exiting the process with code 0.
-    fn force_stop(&self) {
-        process::exit(0);
+    /// Forces the server to stop by closing the TCP listener and every connection.
+    ///
+    /// Unlike `stop`, players aren't sent a disconnect message and the shutdown hooks don't run.
+    async fn force_stop(&self) {
+        self.stop_accepting().await;
+
+        for player in self.get_players().await {
+            player.connection.disconnect().await;
+        }
+
+        self.lifecycle.set(LifecycleState::Stopped);
     }
 
-    /// Returns a vector of players currently connected to the server.
-    fn get_players(&self) -> Ref<'_, Vec<Player>> {
-        self.players.borrow()
+    /// Returns a snapshot of the players currently connected to the server.
+    ///
+    /// The list is copied so the lock isn't held while the caller uses it.
+    async fn get_players(&self) -> Vec<Player> {
+        self.players.lock().await.clone()
     }
 
     /// Retrieves a player by their username.
@@ -192,8 +388,8 @@ impl TickableServer for MinecraftServer {
     ///
     /// An optional reference to the player if found, otherwise None.
     ///
-    fn get_player_username(&self, username: &str) -> Option<Player> {
-        self.get_player_filter(|player| player.username == username).first().cloned()
+    async fn get_player_username(&self, username: &str) -> Option<Player> {
+        self.get_player_filter(|player| player.username == username).await
     }
 
     /// Retrieves a player by their UUID.
@@ -206,8 +402,8 @@ impl TickableServer for MinecraftServer {
     ///
     /// An optional reference to the player if found, otherwise None.
     ///
-    fn get_player_uuid(&self, uuid: UUID) -> Option<Player> {
-        self.get_player_filter(|player| player.uuid == uuid).first().cloned()
+    async fn get_player_uuid(&self, uuid: UUID) -> Option<Player> {
+        self.get_player_filter(|player| player.uuid == uuid).await
     }
 
     /// Returns an optional player that matches the given filter function.
@@ -220,18 +416,33 @@ impl TickableServer for MinecraftServer {
     ///
     /// * `Some(player)` - If a player is found that matches the filter function.
     /// * `None` - If no player is found that matches the filter function.
-    fn get_player_filter(&self, filter: impl Fn(&Player) -> bool) -> Vec<Player> {
-        let mut players = Vec::new();
-        for player in self.get_players().iter() {
-            if filter(player) {
-                players.push(player.clone());
-            }
-        }
+    async fn get_player_filter(&self, filter: impl for<'a> Fn(&'a Player) -> bool + Send) -> Option<Player> {
+        self.find_players(filter).await.into_iter().next()
+    }
 
-        players
+    /// Returns every player that matches the given predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - A closure that takes a reference to a `Player` and returns a `bool`.
+    ///
+    /// # Returns
+    ///
+    /// A snapshot of the matching players, in the order they joined.
+    async fn find_players(&self, predicate: impl for<'a> Fn(&'a Player) -> bool + Send) -> Vec<Player> {
+        self.players
+            .lock()
+            .await
+            .iter()
+            .filter(|player| predicate(player))
+            .cloned()
+            .collect()
     }
 
-    /// Broadcasts a packet to all connected players.
+    /// Broadcasts a packet to all players in the play state.
+    ///
+    /// A failed send doesn't stop the broadcast, so one dead connection can't keep the packet
+    /// from reaching the other players.
     ///
     /// # Arguments
     ///
@@ -239,38 +450,174 @@ impl TickableServer for MinecraftServer {
     ///
     /// # Returns
     ///
-    /// Result indicating success or failure of broadcasting the packet.
+    /// `Ok(())` if every player received the packet, otherwise the first error that occurred.
     ///
     async fn broadcast_packet<P>(&mut self, packet: &P) -> Result<(), PacketError>
     where
         P: Packet + Sync,
     {
-        unimplemented!()
+        let (_, result) = self.send_to_all(packet).await;
+        result
     }
 
-    async fn send_server_packet<P>(&mut self, packet: &P) -> Result<(), PacketError>
+    /// Sends a packet to the connected player with the given UUID.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The UUID of the player to send the packet to.
+    /// * `packet` - The packet to send.
+    ///
+    /// # Returns
+    ///
+    /// `PacketError::UnknownPlayer` if no connected player has the UUID, otherwise the result of the send.
+    ///
+    async fn send_server_packet<P>(&mut self, target: &UUID, packet: &P) -> Result<(), PacketError>
     where
         P: Packet + Sync,
     {
-        unimplemented!()
+        let player = self
+            .get_player_filter(|player| player.uuid == *target)
+            .await
+            .ok_or(PacketError::UnknownPlayer)?;
+
+        player.connection.send_packet(packet).await
     }
 }
 
+/// Disconnects every given player with the same message.
+///
+/// The disconnects run concurrently, so one slow client doesn't hold up the others.
+///
+/// # Arguments
+///
+/// * `players` - The players to disconnect.
+/// * `message` - The reason shown to every player.
+async fn disconnect_players(players: Vec<Player>, message: &str) {
+    let disconnects = players.into_iter().map(|player| {
+        let message = message.to_string();
+        tokio::spawn(async move { player.disconnect(&message).await })
+    });
+
+    for disconnect in disconnects.collect::<Vec<_>>() {
+        let _ = disconnect.await;
+    }
+}
+
+/// Binds a non-blocking listener with `SO_REUSEADDR` set.
+///
+/// # Arguments
+///
+/// * `address` - The address to listen on.
+fn bind_listener(address: SocketAddr) -> io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+
+    Ok(socket.into())
+}
+
+/// Runs `bind` until it succeeds or `attempts` tries have failed.
+///
+/// The task sleeps between attempts, starting with `delay` and doubling it every time.
+///
+/// # Arguments
+///
+/// * `attempts` - The maximum number of attempts, at least one is always made.
+/// * `delay` - The delay before the first retry.
+/// * `bind` - The bind to attempt.
+///
+/// # Returns
+///
+/// The first successful result, or the last error with the number of attempts added to its message.
+async fn bind_with_retry<T>(attempts: u32, delay: Duration, mut bind: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delay = delay;
+    let mut attempt = 1;
+
+    loop {
+        match bind() {
+            Ok(bound) => return Ok(bound),
+            Err(err) if attempt >= attempts => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("failed to bind after {} attempts: {}", attempt, err),
+                ));
+            }
+            Err(err) => {
+                log::warn!("Failed to bind (attempt {}/{}): {}, retrying in {:?}", attempt, attempts, err, delay);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Applies the socket options used for every accepted connection.
+///
+/// With `config.tcp_nodelay`, Nagle's algorithm is disabled (`TCP_NODELAY`) so small,
+/// latency-sensitive packets such as movement and keep-alives are sent straight away instead of
+/// being batched.
+///
+/// # Arguments
+///
+/// * `stream` - The accepted TCP stream.
+/// * `config` - The settings of the server that accepted the stream.
+fn configure_stream(stream: &TcpStream, config: &ServerConfig) -> io::Result<()> {
+    stream.set_nodelay(config.tcp_nodelay)
+}
+
+/// Checks whether a freshly accepted connection is a TCP health check.
+///
+/// Load balancers probe the port by connecting and closing straight away without sending a byte.
+/// Such connections are dropped quietly instead of being turned into a player. So are connections
+/// that send nothing within `timeout`, since a client sends its handshake straight away.
+///
+/// # Arguments
+///
+/// * `stream` - The accepted TCP stream, before anything has been read from it.
+/// * `timeout` - How long to wait for the first byte.
+///
+/// # Returns
+///
+/// `true` if the peer closed (or the socket failed) before sending any data, or sent nothing in time.
+async fn is_health_check(stream: &TcpStream, timeout: Duration) -> bool {
+    let mut first_byte = [0; 1];
+    matches!(
+        tokio::time::timeout(timeout, stream.peek(&mut first_byte)).await,
+        Ok(Ok(0) | Err(_)) | Err(_)
+    )
+}
+
 /// Handles a new connection from a client.
 ///
 /// This function is called when a new TCP connection is established with the server.
-/// It creates a new `Player` struct to represent the connected client, initializes its fields,
-/// and adds it to the list of players in the `MinecraftServer`.
+/// The frames sent by the client are read one at a time and handed to `handle_packet`, which
+/// answers them according to the connection state and decides the next state. The connection is
+/// closed once the client disconnects or `handle_packet` ends it.
 /// It also prints the IP address of the connected client to the console.
 ///
+/// The expected packet order is:
+///
+/// 1. Handshaking: Handshake (0x00), whose next state selects Status (1) or Login (2).
+/// 2. Status: Status Request (0x00) is answered with the Status Response, then Ping (0x01) is
+///    answered with a Pong and the connection is closed.
+/// 3. Login: Login Start (0x00) is answered with Login Success, and Login Acknowledged (0x03)
+///    moves the connection to Configuration.
+/// 4. Configuration: Finish Configuration is sent right away, and its acknowledgement (0x02) moves
+///    the connection to Play.
+/// 5. Play: keep-alives are sent in the background and Keep Alive (0x14) answers are tracked.
+///    Other packets are decoded with the server's `PacketByteConverter`.
+///
 /// # Arguments
 ///
-/// * `stream` - The TCP stream representing the connection with the client.
+/// * `player` - The player of the accepted connection.
 /// * `server` - A mutable reference to the `MinecraftServer` instance.
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// use std::net::TcpStream;
 /// use std::sync::{Arc, Mutex};
 /// use crate::server::{MinecraftServer, Player, UUID};
@@ -279,19 +626,1108 @@ impl TickableServer for MinecraftServer {
 ///     // Implementation omitted for brevity
 /// }
 /// ```
+async fn handle_connection(player: &mut Player, server: &mut MinecraftServer) {
+    log::info!("New connection from {}", player.address());
+
+    let (sender, mut frames) = mpsc::channel(32);
+    let reader = player.connection.reader.clone();
+    let compression_threshold = player.connection.compression_threshold.clone();
+    let address = player.address();
+    let reader = tokio::spawn(async move {
+        // Returning drops `sender`, which ends the loop below and closes the connection.
+        if let Err(err) = PacketRetriever
+            .retrieve_packets(&mut *reader.lock().await, compression_threshold, sender)
+            .await
+        {
+            log::warn!("Closing the connection from {}: {}", address, err);
+        }
+    });
+
+    let mut state = ConnectionState::Handshaking;
+    let mut keep_alive = None;
+    // Handed to the keep-alive loop, which ends the connection by sending on it or dropping it.
+    let (close, mut close_requested) = oneshot::channel();
+    let mut close = Some(close);
+    loop {
+        let (packet_id, packet_data) = tokio::select! {
+            frame = frames.recv() => match frame {
+                Some(frame) => frame,
+                None => break,
+            },
+            _ = &mut close_requested => break,
+        };
+
+        let Some(next_state) = handle_packet(player, server, state, packet_id, packet_data).await else {
+            break;
+        };
+
+        if next_state == ConnectionState::Play && state != ConnectionState::Play {
+            if let Some(close) = close.take() {
+                let interval = server.config.keep_alive_interval;
+                keep_alive = Some(tokio::spawn(keep_alive_loop(player.clone(), interval, close)));
+            }
+        }
+        state = next_state;
+        player.set_state(state);
+    }
+
+    // A client that stopped answering never closes its side, so the reader has to be stopped too.
+    reader.abort();
+    if let Some(keep_alive) = keep_alive {
+        keep_alive.abort();
+    }
+    player.connection.disconnect().await;
+}
+
+/// Handles a single packet received in the given connection state.
 ///
-/// # Panics
+/// # Arguments
 ///
-/// This function will panic if it fails to obtain the peer address of the client.
-async fn handle_connection(player: &mut Player, server: &mut MinecraftServer) {
-    player.connect(server).await.unwrap();
+/// * `player` - The player who sent the packet.
+/// * `server` - The server the player is connected to.
+/// * `state` - The current state of the connection.
+/// * `packet_id` - The ID of the packet.
+/// * `packet_data` - The packet data following the packet ID.
+///
+/// # Returns
+///
+/// The state of the connection after the packet, or `None` if the connection should be closed.
+async fn handle_packet(
+    player: &mut Player,
+    server: &MinecraftServer,
+    state: ConnectionState,
+    packet_id: usize,
+    mut packet_data: BytesMut,
+) -> Option<ConnectionState> {
+    match (state, packet_id) {
+        (ConnectionState::Handshaking, 0x00) => {
+            let handshake = HandshakePacket::decode(&mut packet_data)?;
+
+            match handshake.next_state {
+                VarInt(1) => Some(ConnectionState::Status),
+                VarInt(2) => Some(ConnectionState::Login),
+                _ => None,
+            }
+        }
+        (ConnectionState::Status, 0x00) => {
+            player.send_packet(&status::status_response(server).await).await;
+            Some(state)
+        }
+        (ConnectionState::Status, 0x01) => {
+            if let Some(ping) = PingPacket::decode(&mut packet_data) {
+                player.send_packet(&PongPacket { payload: ping.payload }).await;
+            }
+
+            // The client closes the connection after the pong, so there is nothing left to handle.
+            None
+        }
+        (ConnectionState::Login, 0x00) => {
+            let login_start = LoginStartPacket::decode(&mut packet_data)?;
+
+            let refusal = if player.login_success_sent {
+                Some("Already logged in.")
+            } else if !is_valid_username(&login_start.name) {
+                Some("Invalid username.")
+            } else {
+                None
+            };
+            if let Some(reason) = refusal {
+                log::info!("Refusing the login of {}: {}", player.address(), reason);
+                player.disconnect(reason).await;
+                return None;
+            }
+
+            match log_in(player, server, login_start).await {
+                Ok(()) => Some(state),
+                Err(err) => {
+                    log::warn!("Error logging in {}: {}", player.address(), err);
+                    None
+                }
+            }
+        }
+        (ConnectionState::Login, 0x03) => {
+            LoginAcknowledgedPacket::decode(&mut packet_data)?;
+            // Only a client that was sent Login Success can acknowledge it.
+            if !player.login_success_sent {
+                return None;
+            }
+
+            // Nothing is configured yet, so the configuration ends straight away.
+            match player.connection.send_packet(&FinishConfigurationPacket {}).await {
+                Ok(()) => Some(ConnectionState::Configuration),
+                Err(err) => {
+                    log::warn!("Error configuring {}: {}", player.address(), err);
+                    None
+                }
+            }
+        }
+        (ConnectionState::Configuration, 0x02) => {
+            AcknowledgeFinishConfigurationPacket::decode(&mut packet_data)?;
+            Some(ConnectionState::Play)
+        }
+        (ConnectionState::Play, 0x14) => {
+            let answer = KeepAliveClientPacket::decode(&mut packet_data)?;
+
+            let mismatch = {
+                let mut keep_alive = player.keep_alive.lock().unwrap();
+                (!keep_alive.acknowledge(answer.id)).then(|| keep_alive.mismatch(answer.id, Instant::now()))
+            };
+
+            if let Some(mismatch) = mismatch {
+                log::debug!(
+                    "Dropping {} ({}) after a keep-alive mismatch: {}",
+                    player.username,
+                    player.address(),
+                    mismatch
+                );
+                return None;
+            }
+            Some(state)
+        }
+        _ => {
+            match PacketRetriever
+                .process_packet(&server.converter, state, packet_id, packet_data)
+                .await
+            {
+                Ok(packet) => log::trace!("Received packet {:#04x} in the {:?} state", packet.id(), state),
+                Err(err) => log::debug!("Error processing packet {:#04x}: {}", packet_id, err),
+            }
+            Some(state)
+        }
+    }
+}
+
+/// Sends a keep-alive to the player every `interval` until the connection ends.
+///
+/// The player is disconnected with "Timed out" once `MAX_MISSED_KEEP_ALIVES` keep-alives in a row
+/// go unanswered. Like vanilla, the current time in milliseconds is used as the keep-alive ID.
+///
+/// # Arguments
+///
+/// * `player` - The player to keep alive.
+/// * `interval` - The time between two keep-alives.
+/// * `close` - Tells `handle_connection` to stop reading from the player. It is sent on a timeout
+///   and dropped if a keep-alive can't be sent.
+async fn keep_alive_loop(player: Player, interval: Duration, close: oneshot::Sender<()>) {
+    let mut interval = tokio::time::interval(interval);
+    interval.tick().await; // The first tick completes immediately.
+
+    loop {
+        interval.tick().await;
+
+        let id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as i64);
+        let timed_out = player.keep_alive.lock().unwrap().send(id, Instant::now());
+
+        if timed_out {
+            log::info!("{} missed {} keep-alives, disconnecting.", player.username, MAX_MISSED_KEEP_ALIVES);
+            player.disconnect("Timed out").await;
+            let _ = close.send(());
+            return;
+        }
+
+        if player.connection.send_packet(&KeepAliveServerPacket { id }).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Logs a player in after their Login Start packet, in offline mode.
+///
+/// The player's username and offline UUID are stored on the player and in the server's player
+/// list, then Login Success is sent. The username must already be checked with `is_valid_username`. If the server's config sets a compression threshold,
+/// compression is enabled just before Login Success.
+///
+/// The connection stays in the login state until the client answers with Login Acknowledged,
+/// which moves it to the configuration state. The server then sends Finish Configuration, and the
+/// client's acknowledgement of it moves the connection to the play state, where keep-alives start.
+/// Registry data and the play state's Login packet aren't sent yet, so a vanilla client waits on
+/// the loading screen.
+///
+/// # Arguments
+///
+/// * `player` - The player logging in.
+/// * `server` - The server the player is joining.
+/// * `login_start` - The Login Start packet sent by the client.
+///
+/// # Returns
+///
+/// The error from sending Set Compression or Login Success, if any.
+async fn log_in(
+    player: &mut Player,
+    server: &MinecraftServer,
+    login_start: LoginStartPacket,
+) -> Result<(), PacketError> {
+    // In offline mode the UUID sent by the client is ignored, like vanilla does.
+    player.username = login_start.name;
+    player.uuid = UUID::from_username_offline(&player.username);
+    if let Some(listed) = server
+        .players
+        .lock()
+        .await
+        .iter_mut()
+        .find(|listed| listed.address() == player.address())
+    {
+        listed.username = player.username.clone();
+        listed.uuid = player.uuid;
+    }
+
+    if let Some(threshold) = server.config.compression_threshold {
+        player.connection.enable_compression(threshold).await?;
+    }
 
-    let mut connection = player.connection.connection.lock().await;
-    let peer_addr = connection.peer_addr().unwrap();
-    println!("New connection from {}", peer_addr);
+    player
+        .connection
+        .send_packet(&LoginSuccessPacket {
+            uuid: player.uuid,
+            username: player.username.clone(),
+            property_count: VarInt(0),
+        })
+        .await?;
+    player.login_success_sent = true;
 
-    PacketRetriever.retrieve_packets(&mut connection).await;
+    Ok(())
+}
+
+/// Checks a username against the rules vanilla applies: 1 to 16 characters out of `A-Z`, `a-z`,
+/// `0-9` and `_`.
+///
+/// # Arguments
+///
+/// * `name` - The username sent in Login Start.
+fn is_valid_username(name: &str) -> bool {
+    (1..=16).contains(&name.len()) && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
 }
 
 pub mod client;
+pub mod config;
+pub mod lifecycle;
+pub mod status;
 pub mod tickable_server;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustmc_packets::server::login_start::LoginStartUuid;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn shutdown_hooks_run_once_in_lifo_order() {
+        let server = MinecraftServer::new("127.0.0.1", 0);
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        for id in 0..2 {
+            let order = order.clone();
+            server.on_shutdown(Box::new(move || order.lock().unwrap().push(id)));
+        }
+
+        server.run_shutdown_hooks();
+        server.run_shutdown_hooks();
+
+        assert_eq!(*order.lock().unwrap(), vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn start_reports_address_in_use() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let server = MinecraftServer::new("127.0.0.1", port);
+        let err = server.start().await.unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(err.to_string().contains(&format!("after {} attempts", BIND_ATTEMPTS)));
+        assert_eq!(server.status(), LifecycleState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn start_refuses_a_server_that_is_already_running() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::new("127.0.0.1", port);
+        let accept_loop = server.start().await.unwrap();
+
+        let err = server.start().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(server.status(), LifecycleState::Running);
+        TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+        server.stop().await;
+        accept_loop.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn bind_retries_come_from_the_config() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = MinecraftServer::with_config(ServerConfig {
+            port: taken.local_addr().unwrap().port(),
+            bind_attempts: 2,
+            bind_retry_delay: Duration::from_millis(1),
+            ..ServerConfig::default()
+        });
+
+        let err = server.start().await.unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(err.to_string().contains("after 2 attempts"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn lifecycle_follows_start_and_shutdown() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::new("127.0.0.1", port);
+        assert_eq!(server.status(), LifecycleState::Stopped);
+
+        let states = Arc::new(Mutex::new(Vec::new()));
+        let hook_states = states.clone();
+        let hook_server = (*server).clone();
+        server.on_shutdown(Box::new(move || hook_states.lock().unwrap().push(hook_server.status())));
+
+        server.start().await.unwrap();
+        assert_eq!(server.status(), LifecycleState::Running);
+
+        server.stop().await;
+        assert_eq!(*states.lock().unwrap(), vec![LifecycleState::Stopping]);
+        assert_eq!(server.status(), LifecycleState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn stop_releases_the_port_for_a_restart() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::new("127.0.0.1", port);
+
+        let accept_loop = server.start().await.unwrap();
+        server.stop().await;
+        accept_loop.await.unwrap();
+        assert_eq!(server.status(), LifecycleState::Stopped);
+
+        let accept_loop = server.start().await.unwrap();
+        assert_eq!(server.status(), LifecycleState::Running);
+        TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+        server.force_stop().await;
+        accept_loop.await.unwrap();
+        assert_eq!(server.status(), LifecycleState::Stopped);
+        std::net::TcpListener::bind(("127.0.0.1", port)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn start_right_after_stop_rebinds_the_port() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        // No retries, so the restart fails if the old listener is still open.
+        let server = MinecraftServer::with_config(ServerConfig {
+            port,
+            bind_attempts: 1,
+            ..ServerConfig::default()
+        });
+
+        let first = server.start().await.unwrap();
+        server.stop().await;
+        let second = server.start().await.unwrap();
+
+        first.await.unwrap();
+        assert_eq!(server.status(), LifecycleState::Running);
+        TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+        server.stop().await;
+        second.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn bind_succeeds_once_the_port_is_released() {
+        let mut taken = Some(std::net::TcpListener::bind("127.0.0.1:0").unwrap());
+        let address = taken.as_ref().unwrap().local_addr().unwrap();
+
+        let mut attempts = 0;
+        let listener = bind_with_retry(3, Duration::from_millis(1), || {
+            attempts += 1;
+            let bound = bind_listener(address);
+            // The port is released after the first attempt, like a socket leaving TIME_WAIT.
+            taken = None;
+            bound
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(attempts, 2);
+        assert_eq!(listener.local_addr().unwrap(), address);
+    }
+
+    #[tokio::test]
+    async fn disconnect_players_messages_and_drops_everyone() {
+        use rustmc_packets::server::disconnect::DisconnectPacket;
+        use tokio::io::AsyncReadExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (clients, players) = connected_players(&listener, 2).await;
+        for player in &players {
+            player.set_state(ConnectionState::Play);
+        }
+
+        disconnect_players(players, SHUTDOWN_MESSAGE).await;
+
+        let expected = DisconnectPacket::from_message(SHUTDOWN_MESSAGE).into_protocol_format();
+        for mut client in clients {
+            let mut received = Vec::new();
+            client.read_to_end(&mut received).await.unwrap();
+            assert_eq!(received, expected);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_connections_are_all_listed() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::new("127.0.0.1", port);
+        server.start().await.unwrap();
+
+        let connect = || async move {
+            let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            // Half a frame: enough to not be a health check, and the connection stays open.
+            client.write_all(&[0x10]).await.unwrap();
+            client
+        };
+        let (first, second) = tokio::join!(connect(), connect());
+
+        let mut players = Vec::new();
+        for _ in 0..100 {
+            players = server.get_players().await;
+            if players.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let mut addresses: Vec<_> = players.iter().map(Player::address).collect();
+        addresses.sort();
+        let mut expected = vec![first.local_addr().unwrap(), second.local_addr().unwrap()];
+        expected.sort();
+        assert_eq!(addresses, expected);
+    }
+
+    async fn connected_players(listener: &TcpListener, count: usize) -> (Vec<TcpStream>, Vec<Player>) {
+        let mut clients = Vec::new();
+        let mut players = Vec::new();
+        for _ in 0..count {
+            clients.push(TcpStream::connect(listener.local_addr().unwrap()).await.unwrap());
+            let (stream, address) = listener.accept().await.unwrap();
+            players.push(Player::new(stream, address, "", UUID { data: [0; 16] }));
+        }
+
+        (clients, players)
+    }
+
+    #[tokio::test]
+    async fn broadcast_reaches_every_player() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (clients, players) = connected_players(&listener, 2).await;
+        for player in &players {
+            player.set_state(ConnectionState::Play);
+        }
+        let mut server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        server.players.lock().await.extend(players.clone());
+
+        let packet = PingPacket { payload: 7 };
+        server.broadcast_packet(&packet).await.unwrap();
+        for player in &players {
+            player.connection.disconnect().await;
+        }
+
+        for mut client in clients {
+            let mut received = Vec::new();
+            client.read_to_end(&mut received).await.unwrap();
+            assert_eq!(received, packet.into_protocol_format());
+        }
+    }
+
+    #[tokio::test]
+    async fn broadcast_continues_past_a_dead_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (_clients, players) = connected_players(&listener, 3).await;
+        for player in &players {
+            player.set_state(ConnectionState::Play);
+        }
+        let mut server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        server.players.lock().await.extend(players.clone());
+
+        // The write half of the first player is already shut down, so sending to it fails.
+        players[0].connection.disconnect().await;
+
+        let packet = PingPacket { payload: 7 };
+        assert!(server.broadcast_packet(&packet).await.is_err());
+        assert_eq!(server.broadcast_packet_count(&packet).await, 2);
+    }
+
+    #[tokio::test]
+    async fn broadcast_skips_players_that_are_not_in_play() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (clients, players) = connected_players(&listener, 2).await;
+        players[0].set_state(ConnectionState::Status);
+        players[1].set_state(ConnectionState::Play);
+        let server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        server.players.lock().await.extend(players.clone());
+
+        let packet = PingPacket { payload: 7 };
+        assert_eq!(server.broadcast_packet_count(&packet).await, 1);
+        for player in &players {
+            player.connection.disconnect().await;
+        }
+
+        let expected = [Vec::new(), packet.into_protocol_format()];
+        for (mut client, expected) in clients.into_iter().zip(expected) {
+            let mut received = Vec::new();
+            client.read_to_end(&mut received).await.unwrap();
+            assert_eq!(received, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_server_packet_targets_one_player() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (mut clients, mut players) = connected_players(&listener, 2).await;
+        players[0].uuid = UUID { data: [1; 16] };
+        players[1].uuid = UUID { data: [2; 16] };
+        let mut server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        server.players.lock().await.extend(players.clone());
+
+        let packet = PingPacket { payload: 7 };
+        server.send_server_packet(&UUID { data: [2; 16] }, &packet).await.unwrap();
+        assert!(matches!(
+            server.send_server_packet(&UUID { data: [3; 16] }, &packet).await,
+            Err(PacketError::UnknownPlayer)
+        ));
+        for player in &players {
+            player.connection.disconnect().await;
+        }
+
+        let mut received = Vec::new();
+        clients[1].read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, packet.into_protocol_format());
+        received.clear();
+        clients[0].read_to_end(&mut received).await.unwrap();
+        assert!(received.is_empty());
+    }
+
+    async fn connect_and_log_in(port: u16, username: &str) -> TcpStream {
+        use rustmc_packets::server::login_start::LoginStartPacket;
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let handshake = HandshakePacket {
+            protocol_version: VarInt(764),
+            server_address: "localhost".into(),
+            server_port: port,
+            next_state: VarInt(2),
+        };
+        let login_start = LoginStartPacket {
+            name: username.into(),
+            player_uuid: LoginStartUuid(Some(UUID { data: [0xAB; 16] })),
+        };
+        client.write_all(&handshake.into_protocol_format()).await.unwrap();
+        client.write_all(&login_start.into_protocol_format()).await.unwrap();
+        client
+    }
+
+    async fn wait_for_player(server: &MinecraftServer, username: &str) -> Player {
+        for _ in 0..100 {
+            if let Some(player) = server.get_player_username(username).await {
+                return player;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        panic!("{} never logged in", username);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn players_get_offline_uuids_from_their_username() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::new("127.0.0.1", port);
+        server.start().await.unwrap();
+
+        let alice = connect_and_log_in(port, "Alice").await;
+        let _bob = connect_and_log_in(port, "Bob").await;
+        let alice_uuid = wait_for_player(&server, "Alice").await.uuid;
+        let bob_uuid = wait_for_player(&server, "Bob").await.uuid;
+
+        assert_eq!(alice_uuid, UUID::from_username_offline("Alice"));
+        assert_ne!(alice_uuid, bob_uuid);
+        assert_eq!(server.get_player_uuid(bob_uuid).await.unwrap().username, "Bob");
+
+        // Reconnecting gives the same UUID again.
+        drop(alice);
+        for _ in 0..100 {
+            if server.get_player_username("Alice").await.is_none() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let _alice = connect_and_log_in(port, "Alice").await;
+        assert_eq!(wait_for_player(&server, "Alice").await.uuid, alice_uuid);
+    }
+
+    #[tokio::test]
+    async fn log_in_sends_login_success() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (mut clients, mut players) = connected_players(&listener, 1).await;
+        let server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        server.players.lock().await.push(players[0].clone());
+
+        let login_start = LoginStartPacket { name: "Notch".into(), player_uuid: LoginStartUuid(None) };
+        log_in(&mut players[0], &server, login_start).await.unwrap();
+        players[0].connection.disconnect().await;
+
+        let uuid = UUID::from_username_offline("Notch");
+        assert_eq!(server.get_player_username("Notch").await.unwrap().uuid, uuid);
+
+        let (sender, mut frames) = mpsc::channel(8);
+        PacketRetriever
+            .retrieve_packets(&mut clients[0], Default::default(), sender)
+            .await
+            .unwrap();
+        let (packet_id, packet_data) = frames.recv().await.unwrap();
+        let login_success = LoginSuccessPacket::deserialize(packet_data.to_vec()).unwrap();
+        assert_eq!(packet_id, 0x02);
+        assert_eq!(login_success.uuid, uuid);
+        assert_eq!(login_success.username, "Notch");
+    }
+
+    #[tokio::test]
+    async fn login_enables_the_configured_compression() {
+        use rustmc_packets::server::set_compression::SetCompressionPacket;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (mut clients, mut players) = connected_players(&listener, 1).await;
+        let server = MinecraftServer::with_config(ServerConfig {
+            compression_threshold: Some(64),
+            ..ServerConfig::default()
+        });
+
+        let login_start = LoginStartPacket { name: "Notch".into(), player_uuid: LoginStartUuid(None) };
+        log_in(&mut players[0], &server, login_start).await.unwrap();
+        players[0].connection.disconnect().await;
+        assert_eq!(*players[0].connection.compression_threshold.lock().await, Some(64));
+
+        // Set Compression is the only frame sent before compression starts.
+        let (sender, mut frames) = mpsc::channel(8);
+        PacketRetriever
+            .retrieve_packets(&mut clients[0], Default::default(), sender)
+            .await
+            .unwrap();
+        let (packet_id, packet_data) = frames.recv().await.unwrap();
+        assert_eq!(packet_id, 0x03);
+        assert_eq!(SetCompressionPacket::deserialize(packet_data.to_vec()).unwrap().threshold, VarInt(64));
+    }
+
+    #[tokio::test]
+    async fn with_config_reports_the_configured_status() {
+        let server = MinecraftServer::with_config(ServerConfig {
+            motd: "Custom MOTD".to_string(),
+            max_players: 5,
+            ..ServerConfig::default()
+        });
+        assert_eq!(server.config.motd, "Custom MOTD");
+
+        let response = status::status_response(&server).await;
+        let json: serde_json::Value = serde_json::from_str(&response.json_response).unwrap();
+        assert_eq!(json["description"]["text"], "Custom MOTD");
+        assert_eq!(json["players"]["max"], 5);
+
+        let default = MinecraftServer::new("0.0.0.0", 25566);
+        assert_eq!((default.config.address.as_str(), default.config.port), ("0.0.0.0", 25566));
+        assert_eq!(default.config.motd, status::MOTD);
+        assert_eq!(default.config.compression_threshold, None);
+    }
+
+    /// Keeps every log message with its level, so tests can check what was reported.
+    struct CapturingLogger(Mutex<Vec<(log::Level, String)>>);
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+
+    /// Installs `LOGGER` for every test, which all share it.
+    fn capture_logs() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+    }
+
+    #[tokio::test]
+    async fn keep_alive_mismatch_is_logged_before_dropping() {
+        use rustmc_packets::server::keep_alive_client::KeepAliveClientPacket;
+
+        capture_logs();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (_clients, mut players) = connected_players(&listener, 1).await;
+        let player = &mut players[0];
+        player.username = "Notch".into();
+        player.keep_alive.lock().unwrap().send(8, Instant::now());
+        let server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+
+        let answer = BytesMut::from(&KeepAliveClientPacket { id: 9 }.serialize()[..]);
+        let state = handle_packet(player, &server, ConnectionState::Play, 0x14, answer).await;
+        assert_eq!(state, None);
+
+        let messages = LOGGER.0.lock().unwrap();
+        let diagnostic = messages
+            .iter()
+            .map(|(_, message)| message)
+            .find(|message| message.contains("keep-alive mismatch") && message.contains("Notch"))
+            .expect("the mismatch wasn't logged");
+        assert!(diagnostic.contains("expected keep-alive 8, received 9"), "{}", diagnostic);
+    }
+
+    #[tokio::test]
+    async fn status_counts_only_logged_in_players() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (_clients, players) = connected_players(&listener, 3).await;
+        players[0].set_state(ConnectionState::Status);
+        players[1].set_state(ConnectionState::Login);
+        players[2].set_state(ConnectionState::Play);
+        let server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        server.players.lock().await.extend(players);
+
+        let response = status::status_response(&server).await;
+        let json: serde_json::Value = serde_json::from_str(&response.json_response).unwrap();
+        assert_eq!(json["players"]["online"], 1);
+    }
+
+    #[tokio::test]
+    async fn find_players_returns_every_match() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (_clients, mut players) = connected_players(&listener, 3).await;
+        for (player, username) in players.iter_mut().zip(["Alex", "Steve", "Alice"]) {
+            player.username = username.into();
+        }
+        let server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        server.players.lock().await.extend(players);
+
+        let found = server.find_players(|player| player.username.starts_with('A')).await;
+        let usernames: Vec<_> = found.iter().map(|player| player.username.as_str()).collect();
+
+        assert_eq!(usernames, vec!["Alex", "Alice"]);
+        assert_eq!(server.get_player_filter(|player| player.username.starts_with('A')).await.unwrap().username, "Alex");
+        assert!(server.find_players(|player| player.username.is_empty()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn handle_packet_walks_handshake_login_configuration_and_play() {
+        use rustmc_packets::server::keep_alive_client::KeepAliveClientPacket;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (mut clients, mut players) = connected_players(&listener, 1).await;
+        let player = &mut players[0];
+        let server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        let data = |packet: &dyn Packet| BytesMut::from(&packet.serialize()[..]);
+
+        let mut handshake = HandshakePacket {
+            protocol_version: VarInt(764),
+            server_address: "localhost".into(),
+            server_port: 25565,
+            next_state: VarInt(2),
+        };
+        let state = handle_packet(player, &server, ConnectionState::Handshaking, 0x00, data(&handshake)).await;
+        assert_eq!(state, Some(ConnectionState::Login));
+
+        let login_start = LoginStartPacket { name: "Notch".into(), player_uuid: LoginStartUuid(None) };
+        let state = handle_packet(player, &server, ConnectionState::Login, 0x00, data(&login_start)).await;
+        assert_eq!(state, Some(ConnectionState::Login));
+
+        let state = handle_packet(player, &server, ConnectionState::Login, 0x03, BytesMut::new()).await;
+        assert_eq!(state, Some(ConnectionState::Configuration));
+        let state = handle_packet(player, &server, ConnectionState::Configuration, 0x02, BytesMut::new()).await;
+        assert_eq!(state, Some(ConnectionState::Play));
+
+        let (sender, mut frames) = mpsc::channel(8);
+        tokio::spawn(async move {
+            let _ = PacketRetriever
+                .retrieve_packets(&mut clients[0], Default::default(), sender)
+                .await;
+        });
+        assert_eq!(frames.recv().await.unwrap().0, LoginSuccessPacket::packet_id() as usize);
+        assert_eq!(frames.recv().await.unwrap().0, FinishConfigurationPacket::packet_id() as usize);
+
+        player.keep_alive.lock().unwrap().send(7, Instant::now());
+        let answer = KeepAliveClientPacket { id: 7 };
+        let state = handle_packet(player, &server, ConnectionState::Play, 0x14, data(&answer)).await;
+        assert_eq!(state, Some(ConnectionState::Play));
+        assert_eq!(player.keep_alive.lock().unwrap().pending_id, None);
+
+        player.keep_alive.lock().unwrap().send(8, Instant::now());
+        let wrong_answer = KeepAliveClientPacket { id: 9 };
+        let state = handle_packet(player, &server, ConnectionState::Play, 0x14, data(&wrong_answer)).await;
+        assert_eq!(state, None);
+
+        handshake.next_state = VarInt(3);
+        let state = handle_packet(player, &server, ConnectionState::Handshaking, 0x00, data(&handshake)).await;
+        assert_eq!(state, None);
+    }
+
+    #[tokio::test]
+    async fn login_acknowledged_before_login_start_closes_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (_clients, mut players) = connected_players(&listener, 1).await;
+        let server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+
+        let state = handle_packet(&mut players[0], &server, ConnectionState::Login, 0x03, BytesMut::new()).await;
+        assert_eq!(state, None);
+    }
+
+    #[test]
+    fn usernames_follow_the_vanilla_rules() {
+        for name in ["Notch", "jeb_", "a", "Sixteen_Chars_00"] {
+            assert!(is_valid_username(name), "{}", name);
+        }
+        for name in ["", "Seventeen_Chars_0", "two words", "Nötch", "a-b"] {
+            assert!(!is_valid_username(name), "{}", name);
+        }
+    }
+
+    /// Sends Login Start packets for `names` one after the other, then returns every state
+    /// `handle_packet` returned and everything the client received.
+    async fn log_in_with(names: &[&str]) -> (Vec<Option<ConnectionState>>, Vec<u8>) {
+        use tokio::io::AsyncReadExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (mut clients, mut players) = connected_players(&listener, 1).await;
+        let player = &mut players[0];
+        player.set_state(ConnectionState::Login);
+        let server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+
+        let mut states = Vec::new();
+        for name in names {
+            let login_start = LoginStartPacket { name: name.to_string(), player_uuid: LoginStartUuid(None) };
+            let data = BytesMut::from(&login_start.serialize()[..]);
+            states.push(handle_packet(player, &server, ConnectionState::Login, 0x00, data).await);
+        }
+        player.connection.disconnect().await;
+
+        let mut received = Vec::new();
+        clients[0].read_to_end(&mut received).await.unwrap();
+        (states, received)
+    }
+
+    #[tokio::test]
+    async fn invalid_usernames_get_a_login_disconnect() {
+        use rustmc_packets::server::login_disconnect::LoginDisconnectPacket;
+
+        for name in ["", "two words", "Seventeen_Chars_0"] {
+            let (states, received) = log_in_with(&[name]).await;
+
+            assert_eq!(states, vec![None]);
+            assert_eq!(
+                received,
+                LoginDisconnectPacket::from_message("Invalid username.").into_protocol_format()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn a_second_login_start_gets_a_login_disconnect() {
+        use rustmc_packets::server::login_disconnect::LoginDisconnectPacket;
+
+        let (states, received) = log_in_with(&["Notch", "Herobrine"]).await;
+
+        assert_eq!(states, vec![Some(ConnectionState::Login), None]);
+        let mut expected = LoginSuccessPacket {
+            uuid: UUID::from_username_offline("Notch"),
+            username: "Notch".into(),
+            property_count: VarInt(0),
+        }
+        .into_protocol_format();
+        expected.extend(LoginDisconnectPacket::from_message("Already logged in.").into_protocol_format());
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn fake_client_logs_in_and_reaches_play() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::new("127.0.0.1", port);
+        server.start().await.unwrap();
+
+        let client = connect_and_log_in(port, "Notch").await;
+        let (mut reader, mut writer) = client.into_split();
+        let (sender, mut frames) = mpsc::channel(8);
+        tokio::spawn(async move {
+            PacketRetriever
+                .retrieve_packets(&mut reader, Default::default(), sender)
+                .await
+                .unwrap();
+        });
+
+        let (packet_id, packet_data) = tokio::time::timeout(Duration::from_secs(5), frames.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let login_success = LoginSuccessPacket::deserialize(packet_data.to_vec()).unwrap();
+        assert_eq!(packet_id, 0x02);
+        assert_eq!(login_success.username, "Notch");
+        assert_eq!(login_success.uuid, UUID::from_username_offline("Notch"));
+        let player = wait_for_player(&server, "Notch").await;
+        assert_eq!(player.uuid, login_success.uuid);
+
+        writer.write_all(&LoginAcknowledgedPacket {}.into_protocol_format()).await.unwrap();
+        let (packet_id, _) = frames.recv().await.unwrap();
+        assert_eq!(packet_id, FinishConfigurationPacket::packet_id() as usize);
+
+        writer
+            .write_all(&AcknowledgeFinishConfigurationPacket {}.into_protocol_format())
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if player.state() == ConnectionState::Play {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Notch never reached the play state");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn silent_players_are_removed_after_timing_out() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::with_config(ServerConfig {
+            port,
+            keep_alive_interval: Duration::from_millis(50),
+            ..ServerConfig::default()
+        });
+        server.start().await.unwrap();
+
+        // The client reaches the play state, then never answers a keep-alive and never closes.
+        let mut client = connect_and_log_in(port, "Notch").await;
+        client.write_all(&LoginAcknowledgedPacket {}.into_protocol_format()).await.unwrap();
+        client
+            .write_all(&AcknowledgeFinishConfigurationPacket {}.into_protocol_format())
+            .await
+            .unwrap();
+        wait_for_player(&server, "Notch").await;
+
+        for _ in 0..100 {
+            if server.get_players().await.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the silent player was never removed");
+    }
+
+    #[tokio::test]
+    async fn accepted_connections_use_nodelay() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        configure_stream(&stream, &ServerConfig::default()).unwrap();
+        assert!(stream.nodelay().unwrap());
+
+        let config = ServerConfig {
+            tcp_nodelay: false,
+            ..ServerConfig::default()
+        };
+        configure_stream(&stream, &config).unwrap();
+        assert!(!stream.nodelay().unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn connect_then_close_is_a_health_check() {
+        capture_logs();
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::new("127.0.0.1", port);
+        let accept_loop = server.start().await.unwrap();
+
+        let probe = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let probe_address = probe.local_addr().unwrap().to_string();
+        drop(probe);
+
+        // Stopping waits for the accept loop, but not for the task checking the probe.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        server.stop().await;
+        accept_loop.await.unwrap();
+
+        assert!(server.get_players().await.is_empty());
+        let messages = LOGGER.0.lock().unwrap();
+        let errors: Vec<_> = messages
+            .iter()
+            .filter(|(level, message)| *level == log::Level::Error && message.contains(&probe_address))
+            .collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[tokio::test]
+    async fn health_check_tells_probes_from_clients() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        drop(TcpStream::connect(address).await.unwrap());
+        let (probe, _) = listener.accept().await.unwrap();
+        assert!(is_health_check(&probe, HEALTH_CHECK_TIMEOUT).await);
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        client.write_all(&[0x10]).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(!is_health_check(&stream, HEALTH_CHECK_TIMEOUT).await);
+
+        // A client that never sends a byte doesn't hold the check open.
+        let _silent = TcpStream::connect(address).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(is_health_check(&stream, Duration::from_millis(50)).await);
+    }
+
+    #[tokio::test]
+    async fn answers_status_request_and_ping() {
+        use rustmc_packets::server::{
+            status_request::StatusRequestPacket, status_response::StatusResponsePacket,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, address) = listener.accept().await.unwrap();
+
+        let mut server = (*MinecraftServer::new("127.0.0.1", 0)).clone();
+        let mut player = Player::new(stream, address, "", UUID { data: [0; 16] });
+        tokio::spawn(async move { handle_connection(&mut player, &mut server).await });
+
+        let handshake = HandshakePacket {
+            protocol_version: VarInt(764),
+            server_address: "localhost".into(),
+            server_port: 25565,
+            next_state: VarInt(1),
+        };
+        client.write_all(&handshake.into_protocol_format()).await.unwrap();
+        client.write_all(&StatusRequestPacket {}.into_protocol_format()).await.unwrap();
+        client.write_all(&PingPacket { payload: 42 }.into_protocol_format()).await.unwrap();
+
+        let (sender, mut frames) = mpsc::channel(8);
+        PacketRetriever
+            .retrieve_packets(&mut client, Default::default(), sender)
+            .await
+            .unwrap();
+
+        let (packet_id, packet_data) = frames.recv().await.unwrap();
+        assert_eq!(packet_id, 0x00);
+        let response = StatusResponsePacket::deserialize(packet_data.to_vec()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&response.json_response).unwrap();
+        assert_eq!(json["version"]["protocol"], 764);
+        assert_eq!(json["players"]["online"], 0);
+        assert_eq!(json["description"]["text"], status::MOTD);
+
+        let (packet_id, packet_data) = frames.recv().await.unwrap();
+        assert_eq!(packet_id, 0x01);
+        assert_eq!(PongPacket::deserialize(packet_data.to_vec()).unwrap().payload, 42);
+    }
+}
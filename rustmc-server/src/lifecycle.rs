@@ -0,0 +1,85 @@
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+/// The lifecycle state of a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleState {
+    /// `start` is binding the listener.
+    Starting,
+    /// The listener is bound and connections are accepted.
+    Running,
+    /// `stop` is disconnecting players and running the shutdown hooks.
+    Stopping,
+    /// The server isn't running, either because it was never started or because it stopped.
+    Stopped,
+}
+
+/// The lifecycle state of a server, shared by every clone of it.
+#[derive(Debug, Clone)]
+pub struct Lifecycle(Arc<AtomicU8>);
+
+impl Lifecycle {
+    /// Creates a lifecycle in the `Stopped` state.
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicU8::new(LifecycleState::Stopped as u8)))
+    }
+
+    /// Returns the current state.
+    pub fn get(&self) -> LifecycleState {
+        match self.0.load(Ordering::SeqCst) {
+            0 => LifecycleState::Starting,
+            1 => LifecycleState::Running,
+            2 => LifecycleState::Stopping,
+            _ => LifecycleState::Stopped,
+        }
+    }
+
+    /// Moves to the given state.
+    pub fn set(&self, state: LifecycleState) {
+        self.0.store(state as u8, Ordering::SeqCst);
+    }
+
+    /// Moves from `from` to `to`, unless the state has changed since `from` was read.
+    ///
+    /// # Returns
+    ///
+    /// Whether the state was `from` and was moved to `to`.
+    pub fn transition(&self, from: LifecycleState, to: LifecycleState) -> bool {
+        self.0
+            .compare_exchange(from as u8, to as u8, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_state() {
+        let lifecycle = Lifecycle::new();
+        assert_eq!(lifecycle.get(), LifecycleState::Stopped);
+
+        for state in [LifecycleState::Starting, LifecycleState::Running, LifecycleState::Stopping] {
+            lifecycle.clone().set(state);
+            assert_eq!(lifecycle.get(), state);
+        }
+    }
+
+    #[test]
+    fn transition_only_moves_from_the_expected_state() {
+        let lifecycle = Lifecycle::new();
+
+        assert!(lifecycle.transition(LifecycleState::Stopped, LifecycleState::Starting));
+        assert!(!lifecycle.transition(LifecycleState::Stopped, LifecycleState::Starting));
+        assert_eq!(lifecycle.get(), LifecycleState::Starting);
+    }
+}
@@ -0,0 +1,54 @@
+use rustmc_packets::{server::status_response::StatusResponsePacket, ConnectionState, PROTOCOL_VERSION, VERSION_NAME};
+use serde_json::json;
+
+use crate::MinecraftServer;
+
+/// The default message of the day shown under the server name in the multiplayer list.
+pub const MOTD: &str = "A RustMC Server";
+
+/// The default maximum number of players advertised in the multiplayer list.
+pub const MAX_PLAYERS: usize = 20;
+
+/// Builds the Status Response sent to a client pinging the server.
+///
+/// Only players that finished logging in count as online, so connections still in the handshake,
+/// status or login state (including the one pinging) aren't reported.
+///
+/// # Arguments
+///
+/// * `server` - The server whose version, player count and MOTD are reported.
+///
+/// # Returns
+///
+/// A `StatusResponsePacket` carrying the status JSON.
+pub async fn status_response(server: &MinecraftServer) -> StatusResponsePacket {
+    let response = json!({
+        "version": {
+            "name": VERSION_NAME,
+            "protocol": PROTOCOL_VERSION,
+        },
+        "players": {
+            "max": server.config.max_players,
+            "online": online_players(server).await,
+            "sample": [],
+        },
+        "description": {
+            "text": server.config.motd,
+        },
+    });
+
+    StatusResponsePacket {
+        json_response: response.to_string(),
+    }
+}
+
+/// Counts the players that finished logging in.
+async fn online_players(server: &MinecraftServer) -> usize {
+    server
+        .players
+        .lock()
+        .await
+        .iter()
+        .filter(|player| player.state() == ConnectionState::Play)
+        .count()
+}
@@ -1,8 +1,9 @@
-use std::{cell::Ref, sync::Arc};
+use std::{io, sync::Arc};
 
 use async_trait::async_trait;
 use rustmc_errors::PacketError;
 use rustmc_packets::Packet;
+use tokio::task::JoinHandle;
 
 use crate::client::{uuid::UUID, Player};
 
@@ -22,16 +23,21 @@ pub trait TickableServer {
     fn new(address: &str, port: u16) -> Arc<Self>;
 
     /// Starts the server.
-    fn start(&self);
+    ///
+    /// # Returns
+    ///
+    /// Returns the handle of the task accepting connections, which finishes once the server stops,
+    /// or an error if the listener could not be bound.
+    async fn start(&self) -> io::Result<JoinHandle<()>>;
 
-    /// Stops the server gracefully.
-    fn stop(&self);
+    /// Stops the server gracefully, without exiting the process.
+    async fn stop(&self);
 
-    /// Forces the server to stop immediately.
-    fn force_stop(&self);
+    /// Forces the server to stop immediately, without exiting the process.
+    async fn force_stop(&self);
 
-    /// Returns a reference to the list of players on the server.
-    fn get_players(&self) -> Ref<'_, Vec<Player>>;
+    /// Returns a snapshot of the list of players on the server.
+    async fn get_players(&self) -> Vec<Player>;
 
     /// Returns an `Option` containing the player with the specified username, if found.
     ///
@@ -42,7 +48,7 @@ pub trait TickableServer {
     /// # Returns
     ///
     /// Returns an `Option` containing the player with the specified username, if found.
-    fn get_player_username(&self, username: &str) -> Option<Player>;
+    async fn get_player_username(&self, username: &str) -> Option<Player>;
 
     /// Returns an `Option` containing the player with the specified UUID, if found.
     ///
@@ -53,7 +59,7 @@ pub trait TickableServer {
     /// # Returns
     ///
     /// Returns an `Option` containing the player with the specified UUID, if found.
-    fn get_player_uuid(&self, uuid: UUID) -> Option<Player>;
+    async fn get_player_uuid(&self, uuid: UUID) -> Option<Player>;
 
     /// Returns an `Option` containing the player that matches the specified filter function, if found.
     ///
@@ -64,9 +70,20 @@ pub trait TickableServer {
     /// # Returns
     ///
     /// Returns an `Option` containing the player that matches the specified filter function, if found.
-    fn get_player_filter(&self, filter: impl Fn(&Player) -> bool) -> Vec<Player>;
+    async fn get_player_filter(&self, filter: impl for<'a> Fn(&'a Player) -> bool + Send) -> Option<Player>;
+
+    /// Returns every player that matches the specified predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - The function matching the players.
+    ///
+    /// # Returns
+    ///
+    /// Returns a snapshot of the matching players, in the order they joined.
+    async fn find_players(&self, predicate: impl for<'a> Fn(&'a Player) -> bool + Send) -> Vec<Player>;
 
-    /// Broadcasts a packet to all connected players asynchronously.
+    /// Broadcasts a packet to all players in the play state asynchronously.
     ///
     /// # Arguments
     ///
@@ -79,16 +96,18 @@ pub trait TickableServer {
     where
         P: Packet + Sync;
 
-    /// Sends a packet to the server asynchronously.
+    /// Sends a packet to a single connected player asynchronously.
     ///
     /// # Arguments
     ///
+    /// * `target` - The UUID of the player to send the packet to.
     /// * `packet` - The packet to send.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` indicating whether the send was successful or not.
-    async fn send_server_packet<P>(&mut self, packet: &P) -> Result<(), PacketError>
+    /// Returns `PacketError::UnknownPlayer` if no connected player has the UUID, otherwise
+    /// a `Result` indicating whether the send was successful or not.
+    async fn send_server_packet<P>(&mut self, target: &UUID, packet: &P) -> Result<(), PacketError>
     where
         P: Packet + Sync;
 }
@@ -0,0 +1,139 @@
+//! End-to-end tests that talk to a running `MinecraftServer` over TCP, the way a vanilla client does.
+
+use std::sync::Arc;
+
+use rustmc_packets::{
+    server::{
+        acknowledge_finish_configuration::AcknowledgeFinishConfigurationPacket,
+        finish_configuration::FinishConfigurationPacket, handshake::HandshakePacket,
+        login_acknowledged::LoginAcknowledgedPacket, login_start::{LoginStartPacket, LoginStartUuid},
+        login_success::LoginSuccessPacket, ping::PingPacket, pong::PongPacket,
+        status_request::StatusRequestPacket, status_response::StatusResponsePacket,
+    },
+    types::VarInt,
+    uuid::UUID,
+    ConnectionState, Packet, PacketFrame, PacketRetriever, PROTOCOL_VERSION, VERSION_NAME,
+};
+use rustmc_server::{status, tickable_server::TickableServer, MinecraftServer};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{tcp::OwnedWriteHalf, TcpStream},
+    sync::mpsc::{self, Receiver},
+    task::JoinHandle,
+};
+
+/// A started server and the handle of its accept loop.
+struct TestServer {
+    server: Arc<MinecraftServer>,
+    accept_loop: JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Starts a server on an ephemeral port.
+    async fn start() -> Self {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = MinecraftServer::new("127.0.0.1", port);
+        let accept_loop = server.start().await.unwrap();
+
+        Self { server, accept_loop }
+    }
+
+    /// Connects a client, returning its write half and the frames the server sends it.
+    async fn connect(&self) -> (OwnedWriteHalf, Receiver<PacketFrame>) {
+        let client = TcpStream::connect(("127.0.0.1", self.server.config.port)).await.unwrap();
+        let (mut reader, writer) = client.into_split();
+
+        let (sender, frames) = mpsc::channel(8);
+        tokio::spawn(async move {
+            PacketRetriever
+                .retrieve_packets(&mut reader, Default::default(), sender)
+                .await
+                .unwrap();
+        });
+
+        (writer, frames)
+    }
+
+    /// Builds the handshake a client sends before switching to `next_state`.
+    fn handshake(&self, next_state: i32) -> HandshakePacket {
+        HandshakePacket {
+            protocol_version: VarInt(PROTOCOL_VERSION),
+            server_address: "localhost".into(),
+            server_port: self.server.config.port,
+            next_state: VarInt(next_state),
+        }
+    }
+
+    /// Stops the server and waits for the listener to be released.
+    async fn stop(self) {
+        self.server.stop().await;
+        self.accept_loop.await.unwrap();
+    }
+}
+
+/// Receives the next frame and decodes it as `P`, checking its packet ID.
+async fn expect<P: Packet>(frames: &mut Receiver<PacketFrame>) -> P {
+    let (packet_id, mut packet_data) = frames.recv().await.expect("the server closed the connection");
+    assert_eq!(packet_id, P::packet_id() as usize);
+
+    P::decode(&mut packet_data).expect("the packet didn't decode")
+}
+
+#[tokio::test]
+async fn status_ping_reports_the_server() {
+    let server = TestServer::start().await;
+    let (mut writer, mut frames) = server.connect().await;
+
+    writer.write_all(&server.handshake(1).into_protocol_format()).await.unwrap();
+    writer.write_all(&StatusRequestPacket {}.into_protocol_format()).await.unwrap();
+
+    let response: StatusResponsePacket = expect(&mut frames).await;
+    let json: serde_json::Value = serde_json::from_str(&response.json_response).unwrap();
+    assert_eq!(json["version"]["name"], VERSION_NAME);
+    assert_eq!(json["version"]["protocol"], PROTOCOL_VERSION);
+    assert_eq!(json["players"]["max"], status::MAX_PLAYERS);
+    assert_eq!(json["players"]["online"], 0);
+    assert_eq!(json["description"]["text"], status::MOTD);
+
+    writer.write_all(&PingPacket { payload: 0x1234_5678 }.into_protocol_format()).await.unwrap();
+    let pong: PongPacket = expect(&mut frames).await;
+    assert_eq!(pong.payload, 0x1234_5678);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn login_reaches_the_play_state() {
+    let server = TestServer::start().await;
+    let (mut writer, mut frames) = server.connect().await;
+
+    let login_start = LoginStartPacket {
+        name: "Notch".into(),
+        player_uuid: LoginStartUuid(Some(UUID { data: [0xAB; 16] })),
+    };
+    writer.write_all(&server.handshake(2).into_protocol_format()).await.unwrap();
+    writer.write_all(&login_start.into_protocol_format()).await.unwrap();
+
+    let login_success: LoginSuccessPacket = expect(&mut frames).await;
+    assert_eq!(login_success.username, "Notch");
+    assert_eq!(login_success.uuid, UUID::from_username_offline("Notch"));
+    assert_eq!(login_success.property_count, VarInt(0));
+
+    writer.write_all(&LoginAcknowledgedPacket {}.into_protocol_format()).await.unwrap();
+    let _: FinishConfigurationPacket = expect(&mut frames).await;
+    writer
+        .write_all(&AcknowledgeFinishConfigurationPacket {}.into_protocol_format())
+        .await
+        .unwrap();
+
+    let player = server.server.get_player_username("Notch").await.unwrap();
+    for _ in 0..100 {
+        if player.state() == ConnectionState::Play {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(player.state(), ConnectionState::Play);
+
+    server.stop().await;
+}
@@ -1,23 +1,33 @@
 use std::sync::Arc;
 
-use rustmc_packets::{client::converter::PacketByteConverter, server::handshake::HandshakePacket};
+use rustmc_packets::{server::handshake::HandshakePacket, ConnectionState};
 use rustmc_server::{tickable_server::TickableServer, MinecraftServer};
 use tokio::signal;
 
 #[tokio::main]
 async fn main() {
-    let server: Arc<MinecraftServer> = MinecraftServer::new("127.0.0.1", 8080);
-    server.start();
+    // Info and above is shown by default; set RUST_LOG=debug to also see diagnostics such as
+    // keep-alive mismatches.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    unsafe {
-        let converter = PacketByteConverter;
-        converter.register_packet(HandshakePacket);
+    let server: Arc<MinecraftServer> = MinecraftServer::new("127.0.0.1", 8080);
+    let accept_loop = match server.start().await {
+        Ok(accept_loop) => accept_loop,
+        Err(e) => {
+            log::error!("Error while trying to start RustMC Server: {}", e);
+            return;
+        }
+    };
 
-        let packet_test = converter.get_packet(0x00).await;
-        println!("{:?}", packet_test);
-    }
+    server
+        .converter
+        .register_packet::<HandshakePacket>(ConnectionState::Handshaking)
+        .await;
 
     signal::ctrl_c().await.unwrap();
+    server.stop().await;
+    let _ = accept_loop.await;
 }
 
+#[allow(dead_code)]
 pub(crate) mod misc;
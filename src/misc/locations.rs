@@ -1,5 +1,9 @@
+use rustmc_packets::types::BlockPosition;
+
 /// Represents a location in three-dimensional space, defined by its x, y, z coordinates, yaw, and pitch.
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+///
+/// The default location is the origin, with a yaw and pitch of 0.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
 pub struct Location {
     pub x: f64,
     pub y: f64,
@@ -8,8 +12,60 @@ pub struct Location {
     pub pitch: f32,
 }
 
+impl Location {
+    /// Returns the squared distance to another location, ignoring yaw and pitch.
+    ///
+    /// This avoids the square root of `distance`, so prefer it for comparisons.
+    pub fn distance_squared(&self, other: &Location) -> f64 {
+        let (dx, dy, dz) = (other.x - self.x, other.y - self.y, other.z - self.z);
+        dx * dx + dy * dy + dz * dz
+    }
+
+    /// Returns the distance to another location, ignoring yaw and pitch.
+    pub fn distance(&self, other: &Location) -> f64 {
+        self.distance_squared(other).sqrt()
+    }
+
+    /// Returns this location moved by the offsets of a vector, keeping its yaw and pitch.
+    pub fn add(&self, vector: &Vector) -> Location {
+        Location {
+            x: self.x + vector.offset_x,
+            y: self.y + vector.offset_y,
+            z: self.z + vector.offset_z,
+            ..*self
+        }
+    }
+
+    /// Returns the unit vector pointing from this location to another one.
+    ///
+    /// The vector starts at this location. Its offsets are all 0 if both locations are the same.
+    pub fn direction_to(&self, other: &Location) -> Vector {
+        let offset = Vector {
+            start: *self,
+            offset_x: other.x - self.x,
+            offset_y: other.y - self.y,
+            offset_z: other.z - self.z,
+        };
+
+        offset.normalize().unwrap_or(offset)
+    }
+
+    /// Returns the position of the block containing this location.
+    ///
+    /// Coordinates are floored, so `-0.5` is in block `-1` rather than `0`.
+    pub fn as_block_position(&self) -> BlockPosition {
+        BlockPosition {
+            x: self.x.floor() as i32,
+            y: self.y.floor() as i32,
+            z: self.z.floor() as i32,
+        }
+    }
+}
+
 /// Represents a vector in three-dimensional space, defined by a starting location and offset values in the x, y, and z directions.
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+///
+/// The default vector starts at the origin and has no offset.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
 pub struct Vector {
     pub start: Location,
     pub offset_x: f64,
@@ -17,13 +73,140 @@ pub struct Vector {
     pub offset_z: f64,
 }
 
+impl Vector {
+    /// Returns a vector with the same start and the given offsets.
+    fn with_offsets(&self, offset_x: f64, offset_y: f64, offset_z: f64) -> Vector {
+        Vector {
+            start: self.start,
+            offset_x,
+            offset_y,
+            offset_z,
+        }
+    }
+
+    /// Returns the length of the offsets.
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// Returns the vector scaled to a length of 1, or `None` if its length is 0.
+    pub fn normalize(&self) -> Option<Vector> {
+        let length = self.length();
+        if length == 0.0 {
+            return None;
+        }
+
+        Some(self.scale(1.0 / length))
+    }
+
+    /// Returns the dot product of the offsets of both vectors.
+    pub fn dot(&self, other: &Vector) -> f64 {
+        self.offset_x * other.offset_x
+            + self.offset_y * other.offset_y
+            + self.offset_z * other.offset_z
+    }
+
+    /// Returns the cross product of the offsets of both vectors, following the right-hand rule.
+    ///
+    /// The result starts where this vector starts.
+    pub fn cross(&self, other: &Vector) -> Vector {
+        self.with_offsets(
+            self.offset_y * other.offset_z - self.offset_z * other.offset_y,
+            self.offset_z * other.offset_x - self.offset_x * other.offset_z,
+            self.offset_x * other.offset_y - self.offset_y * other.offset_x,
+        )
+    }
+
+    /// Returns the vector with its offsets multiplied by `factor`.
+    pub fn scale(&self, factor: f64) -> Vector {
+        self.with_offsets(
+            self.offset_x * factor,
+            self.offset_y * factor,
+            self.offset_z * factor,
+        )
+    }
+
+    /// Returns the sum of the offsets of both vectors, starting where this vector starts.
+    pub fn add(&self, other: &Vector) -> Vector {
+        self.with_offsets(
+            self.offset_x + other.offset_x,
+            self.offset_y + other.offset_y,
+            self.offset_z + other.offset_z,
+        )
+    }
+
+    /// Returns the difference of the offsets of both vectors, starting where this vector starts.
+    pub fn sub(&self, other: &Vector) -> Vector {
+        self.with_offsets(
+            self.offset_x - other.offset_x,
+            self.offset_y - other.offset_y,
+            self.offset_z - other.offset_z,
+        )
+    }
+}
+
 /// Represents a bounding box in three-dimensional space, defined by its minimum and maximum locations.
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+///
+/// The default bounding box has no size and sits at the origin.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
 pub struct BoundingBox {
     pub min: Location,
     pub max: Location,
 }
 
+impl BoundingBox {
+    /// Returns whether the location lies inside the box, edges included.
+    pub fn contains(&self, location: &Location) -> bool {
+        (self.min.x..=self.max.x).contains(&location.x)
+            && (self.min.y..=self.max.y).contains(&location.y)
+            && (self.min.z..=self.max.z).contains(&location.z)
+    }
+
+    /// Returns whether the two boxes overlap, touching edges included.
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    /// Returns the distance from the ray's origin to the first point where it enters the box,
+    /// or `None` if it does not reach the box within `ray.distance`.
+    ///
+    /// A ray starting inside the box hits it at distance 0.
+    pub fn intersects_ray(&self, ray: &Ray) -> Option<f64> {
+        let direction = ray.direction.normalize()?;
+        let slabs = [
+            (ray.origin.x, direction.offset_x, self.min.x, self.max.x),
+            (ray.origin.y, direction.offset_y, self.min.y, self.max.y),
+            (ray.origin.z, direction.offset_z, self.min.z, self.max.z),
+        ];
+
+        let mut near = 0.0_f64;
+        let mut far = ray.distance;
+        for (origin, direction, min, max) in slabs {
+            if direction == 0.0 {
+                // Parallel to this slab, so the ray is either always or never between its faces.
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+
+            let (entry, exit) = ((min - origin) / direction, (max - origin) / direction);
+            near = near.max(entry.min(exit));
+            far = far.min(entry.max(exit));
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+}
+
 /// Represents a quaternion, used for representing rotations in three-dimensional space.
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Quaternion {
@@ -33,6 +216,135 @@ pub struct Quaternion {
     pub z: f64,
 }
 
+impl Default for Quaternion {
+    /// Returns the identity rotation.
+    fn default() -> Self {
+        Quaternion::IDENTITY
+    }
+}
+
+impl Quaternion {
+    /// The rotation that leaves every vector unchanged.
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    /// Creates a rotation from angles in degrees: yaw about the y axis, pitch about the x axis
+    /// and roll about the z axis.
+    ///
+    /// Roll is applied to a vector first, then pitch, then yaw.
+    pub fn from_euler(yaw: f32, pitch: f32, roll: f32) -> Quaternion {
+        let half = |degrees: f32| (degrees as f64).to_radians() / 2.0;
+        let (sy, cy) = half(yaw).sin_cos();
+        let (sp, cp) = half(pitch).sin_cos();
+        let (sr, cr) = half(roll).sin_cos();
+
+        let yaw = Quaternion {
+            w: cy,
+            x: 0.0,
+            y: sy,
+            z: 0.0,
+        };
+        let pitch = Quaternion {
+            w: cp,
+            x: sp,
+            y: 0.0,
+            z: 0.0,
+        };
+        let roll = Quaternion {
+            w: cr,
+            x: 0.0,
+            y: 0.0,
+            z: sr,
+        };
+        yaw.multiply(&pitch).multiply(&roll)
+    }
+
+    /// Returns the `(yaw, pitch, roll)` angles in degrees that `from_euler` would turn into this
+    /// rotation.
+    ///
+    /// At a pitch of ±90° yaw and roll turn about the same axis, so roll is reported as 0 and the
+    /// whole turn is given as yaw.
+    pub fn to_euler(self) -> (f32, f32, f32) {
+        let Quaternion { w, x, y, z } = self;
+        let sin_pitch = (-2.0 * (y * z - w * x)).clamp(-1.0, 1.0);
+        let pitch = sin_pitch.asin();
+
+        let (yaw, roll) = if sin_pitch.abs() > 1.0 - 1e-9 {
+            (
+                (-2.0 * (x * z - w * y)).atan2(1.0 - 2.0 * (y * y + z * z)),
+                0.0,
+            )
+        } else {
+            (
+                (2.0 * (x * z + w * y)).atan2(1.0 - 2.0 * (x * x + y * y)),
+                (2.0 * (x * y + w * z)).atan2(1.0 - 2.0 * (x * x + z * z)),
+            )
+        };
+
+        (
+            yaw.to_degrees() as f32,
+            pitch.to_degrees() as f32,
+            roll.to_degrees() as f32,
+        )
+    }
+
+    /// Returns the Hamilton product of both rotations, which applies `other` first and then this
+    /// rotation.
+    pub fn multiply(&self, other: &Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }
+    }
+
+    /// Returns the quaternion scaled to a length of 1, or `None` if its length is 0.
+    pub fn normalize(&self) -> Option<Quaternion> {
+        let length = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        if length == 0.0 {
+            return None;
+        }
+
+        Some(Quaternion {
+            w: self.w / length,
+            x: self.x / length,
+            y: self.y / length,
+            z: self.z / length,
+        })
+    }
+
+    /// Returns the offsets of the vector rotated by this quaternion, keeping its start.
+    ///
+    /// The quaternion is expected to be normalized.
+    pub fn rotate_vector(&self, vector: &Vector) -> Vector {
+        let point = Quaternion {
+            w: 0.0,
+            x: vector.offset_x,
+            y: vector.offset_y,
+            z: vector.offset_z,
+        };
+        let conjugate = Quaternion {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        };
+        let rotated = self.multiply(&point).multiply(&conjugate);
+
+        Vector {
+            start: vector.start,
+            offset_x: rotated.x,
+            offset_y: rotated.y,
+            offset_z: rotated.z,
+        }
+    }
+}
+
 /// Represents a ray in three-dimensional space, defined by its origin, direction, and distance.
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Ray {
@@ -47,4 +359,324 @@ pub struct CoordinateFrame {
     pub x: Vector,
     pub y: Vector,
     pub z: Vector,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(x: f64, y: f64, z: f64) -> Location {
+        Location {
+            x,
+            y,
+            z,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    #[test]
+    fn distance_ignores_rotation() {
+        let from = location(1.0, 2.0, 3.0);
+        let to = Location {
+            yaw: 90.0,
+            pitch: -45.0,
+            ..location(4.0, 6.0, 3.0)
+        };
+
+        assert_eq!(from.distance_squared(&to), 25.0);
+        assert_eq!(from.distance(&to), 5.0);
+        assert_eq!(to.distance(&from), 5.0);
+    }
+
+    #[test]
+    fn add_moves_by_the_vector_offsets() {
+        let from = Location {
+            yaw: 90.0,
+            ..location(1.0, 2.0, 3.0)
+        };
+        let vector = Vector {
+            start: location(0.0, 0.0, 0.0),
+            offset_x: -2.0,
+            offset_y: 0.5,
+            offset_z: 1.0,
+        };
+
+        assert_eq!(
+            from.add(&vector),
+            Location {
+                yaw: 90.0,
+                ..location(-1.0, 2.5, 4.0)
+            }
+        );
+    }
+
+    #[test]
+    fn direction_is_a_unit_vector() {
+        let from = location(1.0, 1.0, 1.0);
+        let direction = from.direction_to(&location(1.0, 1.0, -4.0));
+
+        assert_eq!(direction.start, from);
+        assert_eq!(
+            (direction.offset_x, direction.offset_y, direction.offset_z),
+            (0.0, 0.0, -1.0)
+        );
+
+        let same = from.direction_to(&from);
+        assert_eq!(
+            (same.offset_x, same.offset_y, same.offset_z),
+            (0.0, 0.0, 0.0)
+        );
+    }
+
+    fn vector(offset_x: f64, offset_y: f64, offset_z: f64) -> Vector {
+        Vector {
+            start: location(1.0, 2.0, 3.0),
+            offset_x,
+            offset_y,
+            offset_z,
+        }
+    }
+
+    #[test]
+    fn normalize_gives_a_unit_vector() {
+        let offset = vector(3.0, 4.0, 0.0);
+        assert_eq!(offset.length(), 5.0);
+
+        let unit = offset.normalize().unwrap();
+        assert!((unit.length() - 1.0).abs() < 1e-12);
+        assert!((unit.offset_x - 0.6).abs() < 1e-12);
+        assert!((unit.offset_y - 0.8).abs() < 1e-12);
+        assert_eq!(unit.offset_z, 0.0);
+        assert_eq!(unit.start, offset.start);
+        assert_eq!(vector(0.0, 0.0, 0.0).normalize(), None);
+    }
+
+    #[test]
+    fn cross_follows_the_right_hand_rule() {
+        let (x, y, z) = (
+            vector(1.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+            vector(0.0, 0.0, 1.0),
+        );
+
+        assert_eq!(x.cross(&y), z);
+        assert_eq!(y.cross(&z), x);
+        assert_eq!(z.cross(&x), y);
+        assert_eq!(y.cross(&x), z.scale(-1.0));
+        assert_eq!(x.dot(&x.cross(&y)), 0.0);
+    }
+
+    #[test]
+    fn vectors_add_subtract_and_scale() {
+        let a = vector(1.0, -2.0, 3.0);
+        let b = vector(0.5, 0.5, -1.0);
+
+        assert_eq!(a.add(&b), vector(1.5, -1.5, 2.0));
+        assert_eq!(a.sub(&b), vector(0.5, -2.5, 4.0));
+        assert_eq!(a.scale(2.0), vector(2.0, -4.0, 6.0));
+        assert_eq!(a.dot(&b), 0.5 - 1.0 - 3.0);
+    }
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox {
+            min: location(0.0, 0.0, 0.0),
+            max: location(1.0, 1.0, 1.0),
+        }
+    }
+
+    fn ray(origin: Location, offset_x: f64, offset_y: f64, offset_z: f64) -> Ray {
+        Ray {
+            origin,
+            direction: Vector {
+                start: origin,
+                offset_x,
+                offset_y,
+                offset_z,
+            },
+            distance: 10.0,
+        }
+    }
+
+    #[test]
+    fn ray_hits_the_nearest_face() {
+        let hit = unit_box().intersects_ray(&ray(location(-2.0, 0.5, 0.5), 2.0, 0.0, 0.0));
+        assert_eq!(hit, Some(2.0));
+
+        let short = Ray {
+            distance: 1.5,
+            ..ray(location(-2.0, 0.5, 0.5), 1.0, 0.0, 0.0)
+        };
+        assert_eq!(unit_box().intersects_ray(&short), None);
+    }
+
+    #[test]
+    fn ray_misses_the_box() {
+        let away = ray(location(-2.0, 0.5, 0.5), -1.0, 0.0, 0.0);
+        let beside = ray(location(-2.0, 0.5, 0.5), 1.0, 1.0, 0.0);
+
+        assert_eq!(unit_box().intersects_ray(&away), None);
+        assert_eq!(unit_box().intersects_ray(&beside), None);
+    }
+
+    #[test]
+    fn ray_starting_inside_hits_at_zero() {
+        let inside = ray(location(0.5, 0.5, 0.5), 0.0, -1.0, 0.0);
+        assert_eq!(unit_box().intersects_ray(&inside), Some(0.0));
+    }
+
+    #[test]
+    fn ray_parallel_to_a_face() {
+        let along_face = ray(location(-1.0, 1.0, 0.5), 1.0, 0.0, 0.0);
+        let above_face = ray(location(-1.0, 1.5, 0.5), 1.0, 0.0, 0.0);
+
+        assert_eq!(unit_box().intersects_ray(&along_face), Some(1.0));
+        assert_eq!(unit_box().intersects_ray(&above_face), None);
+    }
+
+    #[test]
+    fn boxes_contain_and_intersect() {
+        let shifted = BoundingBox {
+            min: location(0.5, 0.5, 0.5),
+            max: location(2.0, 2.0, 2.0),
+        };
+        let apart = BoundingBox {
+            min: location(1.5, 0.0, 0.0),
+            max: location(2.0, 1.0, 1.0),
+        };
+
+        assert!(unit_box().contains(&location(1.0, 0.0, 0.5)));
+        assert!(!unit_box().contains(&location(1.1, 0.5, 0.5)));
+        assert!(unit_box().intersects(&shifted));
+        assert!(shifted.intersects(&unit_box()));
+        assert!(!unit_box().intersects(&apart));
+    }
+
+    fn assert_angles_eq(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        assert!(
+            close(actual.0, expected.0)
+                && close(actual.1, expected.1)
+                && close(actual.2, expected.2),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    fn assert_offsets_eq(actual: Vector, expected: Vector) {
+        assert!(
+            actual.sub(&expected).length() < 1e-9,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn euler_angles_round_trip() {
+        for angles in [
+            (0.0, 0.0, 0.0),
+            (30.0, 45.0, 60.0),
+            (-120.0, -10.0, 170.0),
+            (90.0, 0.0, -90.0),
+        ] {
+            assert_angles_eq(
+                Quaternion::from_euler(angles.0, angles.1, angles.2).to_euler(),
+                angles,
+            );
+        }
+    }
+
+    #[test]
+    fn to_euler_folds_roll_into_yaw_at_gimbal_lock() {
+        let locked = Quaternion::from_euler(40.0, 90.0, 25.0);
+        let (yaw, pitch, roll) = locked.to_euler();
+        assert_angles_eq((yaw, pitch, roll), (15.0, 90.0, 0.0));
+
+        let rebuilt = Quaternion::from_euler(yaw, pitch, roll);
+        let forward = vector(0.3, -0.2, 1.0);
+        assert_offsets_eq(
+            rebuilt.rotate_vector(&forward),
+            locked.rotate_vector(&forward),
+        );
+
+        assert_angles_eq(
+            Quaternion::from_euler(40.0, -90.0, 0.0).to_euler(),
+            (40.0, -90.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn rotations_apply_and_compose() {
+        let quarter_yaw = Quaternion::from_euler(90.0, 0.0, 0.0);
+        assert_offsets_eq(
+            quarter_yaw.rotate_vector(&vector(0.0, 0.0, 1.0)),
+            vector(1.0, 0.0, 0.0),
+        );
+        assert_eq!(
+            quarter_yaw.rotate_vector(&vector(0.0, 0.0, 1.0)).start,
+            location(1.0, 2.0, 3.0)
+        );
+
+        let composed = Quaternion::from_euler(30.0, 0.0, 0.0)
+            .multiply(&Quaternion::from_euler(60.0, 0.0, 0.0));
+        assert_angles_eq(composed.to_euler(), (90.0, 0.0, 0.0));
+        assert_eq!(Quaternion::IDENTITY.multiply(&quarter_yaw), quarter_yaw);
+    }
+
+    #[test]
+    fn normalize_quaternion() {
+        let scaled = Quaternion {
+            w: 2.0,
+            x: 0.0,
+            y: 2.0,
+            z: 0.0,
+        };
+        let unit = scaled.normalize().unwrap();
+
+        assert!((unit.w - 0.5_f64.sqrt()).abs() < 1e-12);
+        assert!((unit.y - 0.5_f64.sqrt()).abs() < 1e-12);
+        assert_eq!(
+            Quaternion {
+                w: 0.0,
+                x: 0.0,
+                y: 0.0,
+                z: 0.0
+            }
+            .normalize(),
+            None
+        );
+    }
+
+    #[test]
+    fn defaults_are_zero_sized_at_the_origin() {
+        let origin = location(0.0, 0.0, 0.0);
+
+        assert_eq!(Location::default(), origin);
+        assert_eq!(Vector::default(), Vector { start: origin, ..vector(0.0, 0.0, 0.0) });
+        assert_eq!(BoundingBox::default(), BoundingBox { min: origin, max: origin });
+        assert!(BoundingBox::default().contains(&origin));
+    }
+
+    #[test]
+    fn default_quaternion_leaves_vectors_unchanged() {
+        let offset = vector(0.3, -2.0, 5.5);
+
+        assert_eq!(Quaternion::default(), Quaternion::IDENTITY);
+        assert_eq!(Quaternion::default().rotate_vector(&offset), offset);
+        assert_angles_eq(Quaternion::default().to_euler(), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn block_position_floors_negative_coordinates() {
+        assert_eq!(
+            location(1.9, 64.0, 0.1).as_block_position(),
+            BlockPosition { x: 1, y: 64, z: 0 }
+        );
+        assert_eq!(
+            location(-0.5, -64.2, -1.0).as_block_position(),
+            BlockPosition {
+                x: -1,
+                y: -65,
+                z: -1
+            }
+        );
+    }
+}